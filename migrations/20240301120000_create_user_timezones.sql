@@ -0,0 +1,4 @@
+CREATE TABLE user_timezones (
+    user_id TEXT PRIMARY KEY,
+    tz_name TEXT NOT NULL
+);
@@ -0,0 +1,6 @@
+-- Existing rows were written in the bot host's local time. The bot and the
+-- database share a timezone in the provided deployment, so interpret the old
+-- values in the session timezone when converting.
+ALTER TABLE reminders
+    ALTER COLUMN trigger_time TYPE TIMESTAMPTZ
+    USING trigger_time AT TIME ZONE current_setting('TimeZone');
@@ -0,0 +1,7 @@
+CREATE TABLE sent_reminders (
+    user_id TEXT PRIMARY KEY,
+    message_id TEXT NOT NULL,
+    message_content TEXT NOT NULL,
+    channel_id TEXT NOT NULL,
+    sent_at TIMESTAMPTZ NOT NULL
+);
@@ -0,0 +1,3 @@
+ALTER TABLE reminders ADD COLUMN target_user_id TEXT;
+UPDATE reminders SET target_user_id = user_id;
+ALTER TABLE reminders ALTER COLUMN target_user_id SET NOT NULL;
@@ -0,0 +1 @@
+ALTER TABLE reminders ADD COLUMN status TEXT NOT NULL DEFAULT 'pending';
@@ -0,0 +1 @@
+ALTER TABLE reminders ADD COLUMN attempts INTEGER NOT NULL DEFAULT 0;
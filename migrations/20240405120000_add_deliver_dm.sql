@@ -0,0 +1 @@
+ALTER TABLE reminders ADD COLUMN deliver_dm BOOLEAN NOT NULL DEFAULT FALSE;
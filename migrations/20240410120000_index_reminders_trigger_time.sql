@@ -0,0 +1,3 @@
+-- Every query on trigger_time also filters on status, so one composite index
+-- covers both the due-reminder scan and the next-trigger lookup.
+CREATE INDEX reminders_status_trigger_time_idx ON reminders (status, trigger_time);
@@ -0,0 +1,2 @@
+-- NULL for reminders that repeat until cancelled.
+ALTER TABLE reminders ADD COLUMN remaining_occurrences INTEGER;
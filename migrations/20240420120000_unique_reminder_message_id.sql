@@ -0,0 +1,4 @@
+-- A redelivered message must not set the same reminder twice. Duplicates that
+-- already slipped in are dropped, keeping the first one.
+DELETE FROM reminders a USING reminders b WHERE a.message_id = b.message_id AND a.id > b.id;
+ALTER TABLE reminders ADD CONSTRAINT reminders_message_id_key UNIQUE (message_id);
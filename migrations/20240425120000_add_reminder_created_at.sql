@@ -0,0 +1,3 @@
+-- Reminders set before this have no known creation time and stay NULL.
+ALTER TABLE reminders ADD COLUMN created_at TIMESTAMPTZ;
+ALTER TABLE reminders ALTER COLUMN created_at SET DEFAULT now();
@@ -0,0 +1,2 @@
+-- How the reminder was set, `text` or `slash`. NULL for older reminders.
+ALTER TABLE reminders ADD COLUMN source TEXT;
@@ -0,0 +1,3 @@
+-- A role pinged instead of the target user, who is then the author. NULL for
+-- reminders that ping a user.
+ALTER TABLE reminders ADD COLUMN target_role_id TEXT;
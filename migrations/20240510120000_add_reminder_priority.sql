@@ -0,0 +1,2 @@
+-- Higher priorities fire first when several reminders are due at once.
+ALTER TABLE reminders ADD COLUMN priority SMALLINT NOT NULL DEFAULT 0;
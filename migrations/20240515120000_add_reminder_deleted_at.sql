@@ -0,0 +1,2 @@
+-- Sent reminders are marked deleted instead of removed, and purged later.
+ALTER TABLE reminders ADD COLUMN deleted_at TIMESTAMPTZ;
@@ -0,0 +1,4 @@
+CREATE TABLE user_locales (
+    user_id TEXT PRIMARY KEY,
+    locale TEXT NOT NULL
+);
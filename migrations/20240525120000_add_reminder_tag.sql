@@ -0,0 +1,2 @@
+-- A `#tag` given at the end of a reminder, for filtering `!reminders`.
+ALTER TABLE reminders ADD COLUMN tag TEXT;
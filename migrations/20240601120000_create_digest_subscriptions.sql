@@ -0,0 +1,6 @@
+-- Users who asked for a daily DM listing their next day of reminders.
+CREATE TABLE digest_subscriptions (
+    user_id TEXT PRIMARY KEY,
+    -- In the subscriber's timezone, so each day's digest goes out once.
+    last_sent_on DATE
+);
@@ -0,0 +1,2 @@
+-- When a reminder that failed to send is tried again.
+ALTER TABLE reminders ADD COLUMN retry_at TIMESTAMPTZ;
@@ -0,0 +1,6 @@
+-- Per-server overrides of the global settings, NULL meaning the global value.
+CREATE TABLE guild_settings (
+    guild_id TEXT PRIMARY KEY,
+    prefix TEXT,
+    max_reminders_per_user BIGINT
+);
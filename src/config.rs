@@ -0,0 +1,335 @@
+//! Settings read from the environment once at startup.
+
+use std::env;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use chrono::{NaiveDate, NaiveTime};
+
+#[derive(Debug, PartialEq)]
+pub enum ConfigError {
+    Missing(&'static str),
+    Invalid {
+        name: &'static str,
+        expected: &'static str,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Missing(name) => {
+                write!(
+                    f,
+                    "{} is not set, add it to your .env or the environment",
+                    name
+                )
+            }
+            ConfigError::Invalid { name, expected } => write!(f, "{} must be {}", name, expected),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// No `Debug`, so the token can't end up in a log line.
+pub struct Config {
+    pub token: String,
+    pub db_url: String,
+    pub check_interval: u32,
+    pub max_horizon_years: u32,
+    pub max_send_attempts: i32,
+    /// Seconds after a failed attempt a reminder is tried again.
+    pub retry_interval: u32,
+    /// Hours past its time an undeliverable reminder is retried before it's
+    /// abandoned, `None` for no limit besides `max_send_attempts`.
+    pub max_age_hours: Option<u32>,
+    /// Whether sent one-off reminders are marked deleted, off with
+    /// `DISABLE_CLEANUP`.
+    pub cleanup: bool,
+    /// How long reminders marked deleted are kept before being purged.
+    pub purge_after_days: u32,
+    pub max_reminders_per_user: i64,
+    pub max_message_length: usize,
+    pub cooldown: Duration,
+    pub default_time: NaiveTime,
+    /// Dates skipped along with weekends when counting business days.
+    pub holidays: Vec<NaiveDate>,
+    pub confirm_with_reaction: bool,
+    /// Whether single reminders go out as an embed instead of plain text.
+    pub reminder_embeds: bool,
+    /// Whether to leave out the message intents, so only slash commands and
+    /// direct messages reach the bot.
+    pub slash_only: bool,
+    pub prefix: String,
+    pub db_max_connections: u32,
+    pub db_acquire_timeout: Duration,
+    pub metrics_addr: Option<String>,
+    /// Wording for fired reminders with `{user}`, `{message}` and `{link}`
+    /// placeholders, the built-in wording when unset.
+    pub reminder_template: Option<String>,
+    /// Hour of the day, in each subscriber's timezone, daily digests go out.
+    pub digest_hour: u32,
+    /// How many reminder messages go out before pausing, 0 for no pauses.
+    pub send_batch_size: usize,
+    /// Batch size for reminders that were overdue at startup, 0 to send them
+    /// like any others.
+    pub catch_up_batch_size: usize,
+}
+
+impl Config {
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Self::from_vars(|name| env::var(name).ok())
+    }
+
+    /// Reads every setting through `var`, so tests don't have to touch the
+    /// process environment.
+    pub fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        // `DISCORD_TOKEN=` left blank in a .env is as good as missing.
+        let required = |name| {
+            var(name)
+                .filter(|value| !value.trim().is_empty())
+                .ok_or(ConfigError::Missing(name))
+        };
+        let positive = "a positive integer";
+        let boolean = "true or false";
+
+        Ok(Config {
+            token: required("DISCORD_TOKEN")?,
+            db_url: required("DATABASE_URL")?,
+            check_interval: parse(&var, "CHECK_INTERVAL_SECONDS", 60, positive, |s| *s > 0)?,
+            max_horizon_years: parse(&var, "MAX_REMINDER_YEARS", 5, positive, |y| *y > 0)?,
+            max_send_attempts: parse(&var, "MAX_SEND_ATTEMPTS", 5, positive, |a| *a > 0)?,
+            retry_interval: parse(&var, "RETRY_INTERVAL_SECONDS", 60, positive, |s| *s > 0)?,
+            max_age_hours: match parse(
+                &var,
+                "REMINDER_MAX_AGE_HOURS",
+                24,
+                "a non-negative integer",
+                |_| true,
+            )? {
+                0 => None,
+                hours => Some(hours),
+            },
+            cleanup: !parse(&var, "DISABLE_CLEANUP", false, boolean, |_| true)?,
+            purge_after_days: parse(&var, "PURGE_AFTER_DAYS", 30, positive, |d| *d > 0)?,
+            max_reminders_per_user: parse(&var, "MAX_REMINDERS_PER_USER", 50, positive, |r| {
+                *r > 0
+            })?,
+            // Long enough for most notes while leaving room for the mention and
+            // link under Discord's 2000 character limit.
+            max_message_length: parse(
+                &var,
+                "MAX_MESSAGE_LENGTH",
+                1500,
+                "an integer above 3",
+                |length| *length > 3,
+            )?,
+            cooldown: Duration::from_secs(parse(
+                &var,
+                "REMINDER_COOLDOWN_SECONDS",
+                2,
+                "a non-negative integer",
+                |_| true,
+            )?),
+            default_time: match var("DEFAULT_REMINDER_TIME") {
+                Some(value) => NaiveTime::parse_from_str(&value, "%H:%M").map_err(|_| {
+                    ConfigError::Invalid {
+                        name: "DEFAULT_REMINDER_TIME",
+                        expected: "a time like 09:00",
+                    }
+                })?,
+                None => NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+            },
+            holidays: match var("HOLIDAYS") {
+                Some(value) => parse_holidays(&value).ok_or(ConfigError::Invalid {
+                    name: "HOLIDAYS",
+                    expected: "a comma-separated list of dates like 2024-12-25",
+                })?,
+                None => Vec::new(),
+            },
+            confirm_with_reaction: parse(&var, "CONFIRM_WITH_REACTION", false, boolean, |_| true)?,
+            reminder_embeds: parse(&var, "REMINDER_EMBEDS", false, boolean, |_| true)?,
+            slash_only: parse(&var, "SLASH_ONLY", false, boolean, |_| true)?,
+            prefix: parse(
+                &var,
+                "COMMAND_PREFIX",
+                "!".to_string(),
+                "free of spaces and not empty",
+                |prefix: &String| !prefix.is_empty() && !prefix.contains(char::is_whitespace),
+            )?,
+            // Defaults match sqlx's own so an unconfigured deployment behaves
+            // as before.
+            db_max_connections: parse(&var, "DB_MAX_CONNECTIONS", 10, positive, |c| *c > 0)?,
+            db_acquire_timeout: Duration::from_secs(parse(
+                &var,
+                "DB_ACQUIRE_TIMEOUT_SECONDS",
+                30,
+                positive,
+                |s| *s > 0,
+            )?),
+            metrics_addr: var("METRICS_ADDR"),
+            reminder_template: var("REMINDER_TEMPLATE").filter(|template| !template.is_empty()),
+            digest_hour: parse(&var, "DIGEST_HOUR", 8, "an hour from 0 to 23", |h| *h < 24)?,
+            send_batch_size: parse(&var, "SEND_BATCH_SIZE", 0, "a non-negative integer", |_| {
+                true
+            })?,
+            catch_up_batch_size: parse(
+                &var,
+                "CATCH_UP_BATCH_SIZE",
+                0,
+                "a non-negative integer",
+                |_| true,
+            )?,
+        })
+    }
+}
+
+/// `default` when unset, an error naming the variable when set to something
+/// that doesn't parse or isn't `valid`.
+fn parse<T: FromStr>(
+    var: &impl Fn(&str) -> Option<String>,
+    name: &'static str,
+    default: T,
+    expected: &'static str,
+    valid: impl Fn(&T) -> bool,
+) -> Result<T, ConfigError> {
+    match var(name) {
+        Some(value) => value
+            .parse()
+            .ok()
+            .filter(valid)
+            .ok_or(ConfigError::Invalid { name, expected }),
+        None => Ok(default),
+    }
+}
+
+/// Sorted and without repeats, blank entries are ignored.
+fn parse_holidays(value: &str) -> Option<Vec<NaiveDate>> {
+    let mut holidays = value
+        .split(',')
+        .map(str::trim)
+        .filter(|date| !date.is_empty())
+        .map(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok())
+        .collect::<Option<Vec<_>>>()?;
+    holidays.sort();
+    holidays.dedup();
+    Some(holidays)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn config(vars: &[(&str, &str)]) -> Result<Config, ConfigError> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        Config::from_vars(|name| vars.get(name).cloned())
+    }
+
+    const REQUIRED: [(&str, &str); 2] = [
+        ("DISCORD_TOKEN", "token"),
+        ("DATABASE_URL", "postgres://localhost/remindme"),
+    ];
+
+    #[test]
+    fn missing_required_settings_are_named() {
+        assert_eq!(
+            config(&[]).err(),
+            Some(ConfigError::Missing("DISCORD_TOKEN"))
+        );
+        assert_eq!(
+            config(&[("DISCORD_TOKEN", "token")]).err(),
+            Some(ConfigError::Missing("DATABASE_URL"))
+        );
+        assert_eq!(
+            config(&[
+                ("DISCORD_TOKEN", " "),
+                ("DATABASE_URL", "postgres://localhost")
+            ])
+            .err(),
+            Some(ConfigError::Missing("DISCORD_TOKEN"))
+        );
+        assert_eq!(
+            ConfigError::Missing("DATABASE_URL").to_string(),
+            "DATABASE_URL is not set, add it to your .env or the environment"
+        );
+    }
+
+    #[test]
+    fn unset_settings_use_defaults() {
+        let config = config(&REQUIRED).unwrap();
+        assert_eq!(config.check_interval, 60);
+        assert_eq!(config.max_send_attempts, 5);
+        assert!(config.cleanup);
+        assert_eq!(config.purge_after_days, 30);
+        assert_eq!(config.retry_interval, 60);
+        assert_eq!(config.max_age_hours, Some(24));
+        assert_eq!(config.cooldown, Duration::from_secs(2));
+        assert_eq!(
+            config.default_time,
+            NaiveTime::from_hms_opt(9, 0, 0).unwrap()
+        );
+        assert_eq!(config.prefix, "!");
+        assert!(config.holidays.is_empty());
+        assert_eq!(config.metrics_addr, None);
+        assert_eq!(config.reminder_template, None);
+        assert_eq!(config.send_batch_size, 0);
+        assert_eq!(config.catch_up_batch_size, 0);
+        assert_eq!(config.digest_hour, 8);
+        assert!(!config.reminder_embeds);
+        assert!(!config.slash_only);
+    }
+
+    #[test]
+    fn holidays_are_a_list_of_dates() {
+        let mut vars = REQUIRED.to_vec();
+        vars.push(("HOLIDAYS", "2024-12-26, 2024-12-25,,2024-12-25"));
+        let date = |day| NaiveDate::from_ymd_opt(2024, 12, day).unwrap();
+        assert_eq!(config(&vars).unwrap().holidays, vec![date(25), date(26)]);
+    }
+
+    #[test]
+    fn invalid_settings_say_what_was_expected() {
+        let with = |name, value| {
+            let mut vars = REQUIRED.to_vec();
+            vars.push((name, value));
+            config(&vars).err().map(|e| e.to_string())
+        };
+        assert_eq!(
+            with("CHECK_INTERVAL_SECONDS", "0"),
+            Some("CHECK_INTERVAL_SECONDS must be a positive integer".to_string())
+        );
+        assert_eq!(
+            with("DISABLE_CLEANUP", "yes"),
+            Some("DISABLE_CLEANUP must be true or false".to_string())
+        );
+        assert_eq!(
+            with("COMMAND_PREFIX", "a b"),
+            Some("COMMAND_PREFIX must be free of spaces and not empty".to_string())
+        );
+        assert_eq!(
+            with("DEFAULT_REMINDER_TIME", "9am"),
+            Some("DEFAULT_REMINDER_TIME must be a time like 09:00".to_string())
+        );
+        assert_eq!(
+            with("DIGEST_HOUR", "24"),
+            Some("DIGEST_HOUR must be an hour from 0 to 23".to_string())
+        );
+        assert_eq!(
+            with("RETRY_INTERVAL_SECONDS", "0"),
+            Some("RETRY_INTERVAL_SECONDS must be a positive integer".to_string())
+        );
+        assert_eq!(
+            with("HOLIDAYS", "2024-12-25,christmas"),
+            Some("HOLIDAYS must be a comma-separated list of dates like 2024-12-25".to_string())
+        );
+        assert_eq!(with("REMINDER_MAX_AGE_HOURS", "0"), None);
+        assert_eq!(with("DISABLE_CLEANUP", "true"), None);
+    }
+}
@@ -0,0 +1,89 @@
+//! Translations of the most common replies, English when there's none.
+
+pub const DEFAULT_LOCALE: &str = "en";
+
+/// Every locale `!locale` accepts, each with a full set of translations.
+pub const LOCALES: &[&str] = &["en", "pl", "de", "es"];
+
+/// The text for `key` in `locale`, falling back to English and then to the
+/// key itself.
+pub fn t<'a>(locale: &str, key: &'a str) -> &'a str {
+    translation(locale, key)
+        .or_else(|| translation(DEFAULT_LOCALE, key))
+        .unwrap_or(key)
+}
+
+/// The supported locale for a code like `pl` or `pl-PL`, in any case.
+pub fn supported_locale(code: &str) -> Option<&'static str> {
+    let language = code.split(['-', '_']).next()?.to_lowercase();
+    LOCALES.iter().copied().find(|locale| *locale == language)
+}
+
+fn translation(locale: &str, key: &str) -> Option<&'static str> {
+    let text = match (locale, key) {
+        ("en", "reminder_set") => "Reminder set for",
+        ("en", "invalid_date") => "I couldn't understand the date",
+        ("en", "no_reminders") => "You have no pending reminders",
+        ("en", "locale_set") => "I'll reply in English from now on",
+
+        ("pl", "reminder_set") => "Przypomnienie ustawione na",
+        ("pl", "invalid_date") => "Nie rozumiem daty",
+        ("pl", "no_reminders") => "Nie masz żadnych oczekujących przypomnień",
+        ("pl", "locale_set") => "Od teraz odpowiadam po polsku",
+
+        ("de", "reminder_set") => "Erinnerung gestellt für",
+        ("de", "invalid_date") => "Ich habe das Datum nicht verstanden",
+        ("de", "no_reminders") => "Du hast keine ausstehenden Erinnerungen",
+        ("de", "locale_set") => "Ab jetzt antworte ich auf Deutsch",
+
+        ("es", "reminder_set") => "Recordatorio programado para",
+        ("es", "invalid_date") => "No entendí la fecha",
+        ("es", "no_reminders") => "No tienes recordatorios pendientes",
+        ("es", "locale_set") => "A partir de ahora respondo en español",
+
+        _ => return None,
+    };
+    Some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEYS: [&str; 4] = ["reminder_set", "invalid_date", "no_reminders", "locale_set"];
+
+    #[test]
+    fn translates_known_locales() {
+        assert_eq!(
+            t("pl", "no_reminders"),
+            "Nie masz żadnych oczekujących przypomnień"
+        );
+        assert_eq!(t("en", "reminder_set"), "Reminder set for");
+        for locale in LOCALES {
+            for key in KEYS {
+                assert!(
+                    translation(locale, key).is_some(),
+                    "{} has no {}",
+                    locale,
+                    key
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_locales_and_keys_fall_back() {
+        assert_eq!(t("xx", "no_reminders"), "You have no pending reminders");
+        assert_eq!(t("", "reminder_set"), "Reminder set for");
+        assert_eq!(t("pl", "no_such_key"), "no_such_key");
+    }
+
+    #[test]
+    fn locale_codes_are_matched_by_language() {
+        assert_eq!(supported_locale("pl"), Some("pl"));
+        assert_eq!(supported_locale("PL-pl"), Some("pl"));
+        assert_eq!(supported_locale("de_AT"), Some("de"));
+        assert_eq!(supported_locale("fr"), None);
+        assert_eq!(supported_locale(""), None);
+    }
+}
@@ -0,0 +1,4 @@
+pub mod config;
+pub mod i18n;
+pub mod parser;
+pub mod store;
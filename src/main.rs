@@ -1,272 +1,5323 @@
-use std::time::Duration;
-use std::{env, sync::Arc};
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+use axum::extract::State;
+use axum::http::{header, StatusCode};
+use axum::routing::get;
+use axum::Router;
 use clokwerk::{AsyncScheduler, TimeUnits};
-use dotenv;
-use regex::Regex;
+use futures::FutureExt;
 
-use chrono::{Local, NaiveDateTime};
+use chrono::{DateTime, Months, NaiveDate, Timelike, Utc};
+use chrono_tz::Tz;
+use regex::{Captures, Regex};
 
-use serenity::all::{ChannelId, MessageId, UserId};
+use serde::{Deserialize, Serialize};
+use serenity::all::{
+    ButtonStyle, Channel, ChannelId, ChannelType, Command, CommandOptionType, ComponentInteraction,
+    CreateActionRow, CreateAllowedMentions, CreateAttachment, CreateButton, CreateCommand,
+    CreateCommandOption, CreateEmbed, CreateInteractionResponse, CreateInteractionResponseMessage,
+    CreateMessage, EditInteractionResponse, GuildId, Interaction, Mention, MessageId, Permissions,
+    ResolvedOption, ResolvedValue, RoleId, User, UserId,
+};
 use serenity::model::channel::Message;
 use serenity::model::gateway::Ready;
 use serenity::prelude::*;
 use serenity::utils::MessageBuilder;
 use serenity::{async_trait, http::Http};
-use sqlx::{FromRow, PgPool};
+use sqlx::postgres::PgPoolOptions;
+use sqlx::PgPool;
+use tokio::sync::{watch, Mutex};
+use tracing::{debug, error, info, instrument, warn};
+use tracing_subscriber::EnvFilter;
+
+use remindme_bot::config::Config;
+use remindme_bot::i18n::{supported_locale, t, DEFAULT_LOCALE, LOCALES};
+use remindme_bot::parser::{
+    anchored_trigger_time, command_name, cron_expression, is_cancel_all, is_export_command,
+    is_import_command, is_list_alias, is_test_command, next_recurrence_after,
+    parse_anchored_command, parse_cancel_command, parse_cancel_text_command, parse_date_str,
+    parse_digest_command, parse_edit_command, parse_locale_command, parse_reminder_command,
+    parse_reminders_tag, parse_reschedule_command, parse_settings_command, parse_timezone_command,
+    parse_upcoming_command, parse_whenis_command, snooze_until, uses_legacy_minutes,
+    AnchoredCommand, DateParseError, ReminderCommand, SettingsCommand, MAX_PREFIX_LENGTH,
+};
+use remindme_bot::store::{
+    cancel_all_reminders_for_user, cancel_reminder_for_user, claim_reminder,
+    count_overdue_reminders, count_reminders_due_between, count_user_reminders, delete_reminder,
+    delete_user_reminder, find_user_reminders_by_text, get_digest_subscribers, get_due_reminders,
+    get_guild_settings, get_metrics, get_reminder_stats, get_reminders_due_between,
+    get_user_locale, get_user_reminders, get_user_timezone, insert_reminder, mark_digest_sent,
+    mark_reminder_failed, missing_columns, purge_deleted_reminders, record_failed_attempt,
+    record_sent_reminder, reminder_table_columns, reschedule_reminder, resolve_user_reminder,
+    set_digest, set_guild_prefix, set_guild_reminder_limit, set_next_trigger_time, set_user_locale,
+    set_user_timezone, snooze_last_reminder, undo_last_reminder, update_reminder_text,
+    DigestSubscriber, GuildSettings, Metrics, Reminder, ReminderStats, PRIORITY_HIGH,
+    PRIORITY_NORMAL, REMINDER_COLUMNS, SOURCE_SLASH, SOURCE_TEXT, STATUS_PENDING,
+};
 
 struct Handler {
     pool: PgPool,
+    config: Config,
+    last_reminder_set: Mutex<HashMap<UserId, Instant>>,
+    reminder_lists: Mutex<HashMap<MessageId, ReminderListPages>>,
+    /// Servers' overrides, dropped when they're changed with `settings`.
+    guild_settings: Mutex<HashMap<GuildId, GuildSettings>>,
+}
+
+impl Handler {
+    /// Whether the user set a reminder too recently, starting a new cooldown
+    /// when they didn't.
+    async fn on_cooldown(&self, user_id: UserId) -> bool {
+        let now = Instant::now();
+        let mut last_reminder_set = self.last_reminder_set.lock().await;
+        if on_cooldown(
+            last_reminder_set.get(&user_id).copied(),
+            now,
+            self.config.cooldown,
+        ) {
+            return true;
+        }
+        // Expired entries are dropped so the map doesn't grow with every user seen.
+        last_reminder_set.retain(|_, last| !is_expired(*last, now, self.config.cooldown));
+        last_reminder_set.insert(user_id, now);
+        false
+    }
+
+    async fn user_locale(&self, user_id: &str) -> String {
+        match get_user_locale(&self.pool, user_id).await {
+            Ok(locale) => locale.unwrap_or_else(|| DEFAULT_LOCALE.to_string()),
+            Err(e) => {
+                warn!("Error getting locale, using English: {:?}", e);
+                DEFAULT_LOCALE.to_string()
+            }
+        }
+    }
+
+    async fn guild_settings(&self, guild_id: Option<GuildId>) -> GuildSettings {
+        let Some(guild_id) = guild_id else {
+            return GuildSettings::default();
+        };
+        if let Some(settings) = self.guild_settings.lock().await.get(&guild_id) {
+            return settings.clone();
+        }
+        match get_guild_settings(&self.pool, &guild_id.to_string()).await {
+            Ok(settings) => {
+                self.guild_settings
+                    .lock()
+                    .await
+                    .insert(guild_id, settings.clone());
+                settings
+            }
+            // Not cached, so the next message tries again.
+            Err(e) => {
+                warn!(
+                    "Error getting server settings, using the global ones: {:?}",
+                    e
+                );
+                GuildSettings::default()
+            }
+        }
+    }
+
+    async fn server_settings(&self, guild_id: Option<GuildId>) -> ServerSettings {
+        resolve_settings(&self.config, self.guild_settings(guild_id).await)
+    }
+
+    async fn user_timezone(&self, user_id: &str) -> Option<Tz> {
+        match get_user_timezone(&self.pool, user_id).await {
+            Ok(timezone) => timezone,
+            Err(e) => {
+                warn!("Error getting timezone, using server timezone: {:?}", e);
+                None
+            }
+        }
+    }
+
+    /// Keeps track of a paged `reminders` list so its buttons can turn pages.
+    async fn remember_list(&self, message_id: MessageId, user_id: UserId, tag: Option<String>) {
+        let now = Instant::now();
+        let mut lists = self.reminder_lists.lock().await;
+        lists.retain(|_, list| !is_expired(list.last_used, now, LIST_PAGES_TTL));
+        lists.insert(
+            message_id,
+            ReminderListPages {
+                user_id,
+                tag,
+                page: 0,
+                last_used: now,
+            },
+        );
+    }
+
+    async fn turn_list_page(&self, ctx: &Context, component: &ComponentInteraction) {
+        let step: isize = match component.data.custom_id.as_str() {
+            PREVIOUS_PAGE_ID => -1,
+            NEXT_PAGE_ID => 1,
+            _ => return,
+        };
+        let now = Instant::now();
+        let list = {
+            let mut lists = self.reminder_lists.lock().await;
+            match lists.get_mut(&component.message.id) {
+                Some(list) if is_expired(list.last_used, now, LIST_PAGES_TTL) => None,
+                Some(list) if list.user_id != component.user.id => {
+                    let response = CreateInteractionResponseMessage::new()
+                        .content("Only the person who asked for this list can turn its pages")
+                        .ephemeral(true);
+                    let _ = component
+                        .create_response(&ctx.http, CreateInteractionResponse::Message(response))
+                        .await;
+                    return;
+                }
+                Some(list) => {
+                    list.page = list.page.saturating_add_signed(step);
+                    list.last_used = now;
+                    Some((list.page, list.tag.clone()))
+                }
+                None => None,
+            }
+        };
+        let Some((page, tag)) = list else {
+            let prefix = self.server_settings(component.guild_id).await.prefix;
+            let response = CreateInteractionResponseMessage::new()
+                .content(format!(
+                    "This list has expired, use `{}reminders` again",
+                    prefix
+                ))
+                .ephemeral(true);
+            let _ = component
+                .create_response(&ctx.http, CreateInteractionResponse::Message(response))
+                .await;
+            return;
+        };
+
+        let user_id = component.user.id.to_string();
+        let reminders = match get_user_reminders(&self.pool, &user_id).await {
+            Ok(reminders) => reminders,
+            Err(e) => {
+                error!("Error getting reminders: {:?}", e);
+                return;
+            }
+        };
+        let timezone = self.user_timezone(&user_id).await;
+        let locale = self.user_locale(&user_id).await;
+        // The list may have shrunk since it was sent.
+        let listed = tagged_reminders(&reminders, tag.as_deref());
+        let (page, _) = page_of(&listed, page);
+        if let Some(list) = self
+            .reminder_lists
+            .lock()
+            .await
+            .get_mut(&component.message.id)
+        {
+            list.page = page;
+        }
+        let pages = page_count(listed.len());
+        let response = CreateInteractionResponseMessage::new()
+            .content(format_reminder_list(
+                &reminders,
+                tag.as_deref(),
+                timezone,
+                page,
+                &locale,
+            ))
+            .components(if pages > 1 {
+                page_buttons(page, pages)
+            } else {
+                Vec::new()
+            });
+        if let Err(e) = component
+            .create_response(
+                &ctx.http,
+                CreateInteractionResponse::UpdateMessage(response),
+            )
+            .await
+        {
+            error!("Error turning reminder list page: {:?}", e);
+        }
+    }
+
+    async fn at_reminder_limit(&self, user_id: &str, limit: i64) -> bool {
+        match count_user_reminders(&self.pool, user_id).await {
+            Ok(count) => reached_reminder_limit(count, limit),
+            Err(e) => {
+                warn!("Error counting reminders, allowing new reminder: {:?}", e);
+                false
+            }
+        }
+    }
 }
 
 #[async_trait]
 impl EventHandler for Handler {
     async fn message(&self, ctx: Context, msg: Message) {
-        if msg.content == "!help" {
-            let help_message = MessageBuilder::new()
-        .push("I can remind you about something in the future. ")
-        .push("To set a reminder, use the `!remindme` command followed by a date and time. ")
-        .push("For example, `!remindme 2021-01-01-12-00` or `!remindme 1d` ")
-        .push("You can also add a message to the reminder, like this: `!remindme 2021-01-01-12-00 don't forget to call mom`")
-        .build();
-            let _ = msg.channel_id.say(&ctx.http, &help_message).await;
+        if is_ignored_author(&msg.author, ctx.cache.current_user().id) {
+            return;
+        }
+
+        let settings = self.server_settings(msg.guild_id).await;
+        let prefix = settings.prefix.as_str();
+        let command = command_name(&msg.content, prefix).unwrap_or_default();
+        if command == "help" {
+            for part in split_message(&help_message(prefix), DISCORD_MESSAGE_LIMIT) {
+                let _ = msg.channel_id.say(&ctx.http, part).await;
+            }
+            return;
+        }
+
+        if command == "remindstats" {
+            let is_admin = match author_permissions(&ctx.http, &msg).await {
+                Ok(permissions) => permissions.administrator(),
+                Err(e) => {
+                    error!("Error checking permissions: {:?}", e);
+                    return;
+                }
+            };
+            if !is_admin {
+                let _ = msg.channel_id.say(&ctx.http, ADMIN_ONLY_MESSAGE).await;
+                return;
+            }
+            let response = match get_reminder_stats(&self.pool).await {
+                Ok(stats) => format_reminder_stats(&stats),
+                Err(e) => {
+                    error!("Error getting reminder stats: {:?}", e);
+                    return;
+                }
+            };
+            let _ = msg.channel_id.say(&ctx.http, &response).await;
+            return;
+        }
+
+        if command == "digest" {
+            let response = match parse_digest_command(&msg.content, prefix) {
+                Some(enabled) => {
+                    match set_digest(&self.pool, &msg.author.id.to_string(), enabled).await {
+                        Ok(()) => digest_set_message(enabled, self.config.digest_hour),
+                        Err(e) => {
+                            error!("Error setting digest: {:?}", e);
+                            return;
+                        }
+                    }
+                }
+                None => format!("Usage: `{p}digest on` or `{p}digest off`", p = prefix),
+            };
+            let _ = msg.channel_id.say(&ctx.http, response).await;
+            return;
+        }
+
+        if command == "settings" {
+            let Some(guild_id) = msg.guild_id else {
+                let _ = msg.channel_id.say(&ctx.http, SERVER_ONLY_MESSAGE).await;
+                return;
+            };
+            let is_admin = match author_permissions(&ctx.http, &msg).await {
+                Ok(permissions) => permissions.administrator(),
+                Err(e) => {
+                    error!("Error checking permissions: {:?}", e);
+                    return;
+                }
+            };
+            if !is_admin {
+                let _ = msg.channel_id.say(&ctx.http, ADMIN_ONLY_MESSAGE).await;
+                return;
+            }
+            let Some(settings_command) = parse_settings_command(&msg.content, prefix) else {
+                let _ = msg.channel_id.say(&ctx.http, settings_usage(prefix)).await;
+                return;
+            };
+
+            let guild = guild_id.to_string();
+            let saved = match &settings_command {
+                SettingsCommand::Show => Ok(()),
+                SettingsCommand::Prefix(new_prefix) => {
+                    set_guild_prefix(&self.pool, &guild, new_prefix.as_deref()).await
+                }
+                SettingsCommand::ReminderLimit(limit) => {
+                    set_guild_reminder_limit(&self.pool, &guild, *limit).await
+                }
+            };
+            if let Err(e) = saved {
+                error!("Error saving server settings: {:?}", e);
+                return;
+            }
+            if settings_command != SettingsCommand::Show {
+                info!(%guild_id, ?settings_command, "Server settings changed");
+                self.guild_settings.lock().await.remove(&guild_id);
+            }
+            let settings = self.server_settings(Some(guild_id)).await;
+            let _ = msg
+                .channel_id
+                .say(&ctx.http, format_server_settings(&settings))
+                .await;
+            return;
+        }
+
+        if command == "upcoming" {
+            let is_admin = match author_permissions(&ctx.http, &msg).await {
+                Ok(permissions) => permissions.administrator(),
+                Err(e) => {
+                    error!("Error checking permissions: {:?}", e);
+                    return;
+                }
+            };
+            if !is_admin {
+                let _ = msg.channel_id.say(&ctx.http, ADMIN_ONLY_MESSAGE).await;
+                return;
+            }
+            let hours = match parse_upcoming_command(&msg.content, prefix) {
+                Some(hours) if (1..=MAX_UPCOMING_HOURS).contains(&hours) => hours,
+                _ => {
+                    let _ = msg.channel_id.say(&ctx.http, upcoming_usage(prefix)).await;
+                    return;
+                }
+            };
+            let now = Utc::now();
+            let until = now + chrono::Duration::hours(hours.into());
+            let counts = match count_reminders_due_between(&self.pool, now, until).await {
+                Ok(counts) => counts,
+                Err(e) => {
+                    error!("Error counting upcoming reminders: {:?}", e);
+                    return;
+                }
+            };
+            let response = format_upcoming(hours, &counts);
+            for part in split_message(&response, DISCORD_MESSAGE_LIMIT) {
+                let _ = msg.channel_id.say(&ctx.http, part).await;
+            }
+            return;
+        }
+
+        if command == "whenis" {
+            let Some(date_str) = parse_whenis_command(&msg.content, prefix) else {
+                let _ = msg.channel_id.say(&ctx.http, whenis_usage(prefix)).await;
+                return;
+            };
+            let user_id = msg.author.id.to_string();
+            let timezone = self.user_timezone(&user_id).await;
+            let now = Utc::now();
+            let response = match parse_date_str(
+                &date_str,
+                timezone,
+                now,
+                self.config.default_time,
+                &self.config.holidays,
+            ) {
+                Ok(trigger_time) => resolved_time_message(trigger_time, timezone, now),
+                Err(e) => {
+                    let locale = self.user_locale(&user_id).await;
+                    date_error_message(&e, &date_str, prefix, &locale)
+                }
+            };
+            let _ = msg.channel_id.say(&ctx.http, response).await;
+            return;
+        }
+
+        if command == "reminders" || is_list_alias(&msg.content, prefix) {
+            let tag = parse_reminders_tag(&msg.content, prefix);
+            let timezone = self.user_timezone(&msg.author.id.to_string()).await;
+            let locale = self.user_locale(&msg.author.id.to_string()).await;
+            let reminders = match get_user_reminders(&self.pool, &msg.author.id.to_string()).await {
+                Ok(reminders) => reminders,
+                Err(e) => {
+                    error!("Error getting reminders: {:?}", e);
+                    return;
+                }
+            };
+            let mut message = CreateMessage::new().content(format_reminder_list(
+                &reminders,
+                tag.as_deref(),
+                timezone,
+                0,
+                &locale,
+            ));
+            let pages = page_count(tagged_reminders(&reminders, tag.as_deref()).len());
+            if pages > 1 {
+                message = message.components(page_buttons(0, pages));
+            }
+            match msg.channel_id.send_message(&ctx.http, message).await {
+                Ok(sent) if pages > 1 => self.remember_list(sent.id, msg.author.id, tag).await,
+                Ok(_) => {}
+                Err(e) => error!("Error listing reminders: {:?}", e),
+            }
+            return;
+        }
+
+        if command == "clearreminders" || is_cancel_all(&msg.content, prefix) {
+            let response =
+                match cancel_all_reminders_for_user(&self.pool, &msg.author.id.to_string()).await {
+                    Ok(count) => cancelled_all_message(count),
+                    Err(e) => {
+                        error!("Error cancelling all reminders: {:?}", e);
+                        return;
+                    }
+                };
+            let _ = msg.channel_id.say(&ctx.http, &response).await;
             return;
         }
-        if msg.author.bot {
+
+        if is_export_command(&msg.content, prefix) {
+            let user_id = msg.author.id.to_string();
+            let reminders = match get_user_reminders(&self.pool, &user_id).await {
+                Ok(reminders) => reminders,
+                Err(e) => {
+                    error!("Error getting reminders: {:?}", e);
+                    return;
+                }
+            };
+            if reminders.is_empty() {
+                let _ = msg
+                    .channel_id
+                    .say(&ctx.http, NOTHING_TO_EXPORT_MESSAGE)
+                    .await;
+                return;
+            }
+            let timezone = self.user_timezone(&user_id).await;
+            let file = match serenity::json::to_vec_pretty(&export_reminders(&reminders, timezone))
+            {
+                Ok(file) => file,
+                Err(e) => {
+                    error!("Error exporting reminders: {:?}", e);
+                    return;
+                }
+            };
+            let message = CreateMessage::new()
+                .content(format!("Your {} pending reminders", reminders.len()))
+                .add_file(CreateAttachment::bytes(file, EXPORT_FILE_NAME));
+            if let Err(e) = msg.channel_id.send_message(&ctx.http, message).await {
+                error!("Error sending exported reminders: {:?}", e);
+            }
             return;
         }
 
-        if let Some((date_str, text)) = parse_reminder_command(&msg.content) {
-            if let Some(trigger_time) = parse_date_str(&date_str) {
-                println!("Setting reminder for {:?}", trigger_time);
+        if is_import_command(&msg.content, prefix) {
+            let Some(attachment) = msg.attachments.first() else {
+                let _ = msg.channel_id.say(&ctx.http, IMPORT_USAGE_MESSAGE).await;
+                return;
+            };
+            if attachment.size > MAX_IMPORT_FILE_SIZE {
+                let _ = msg
+                    .channel_id
+                    .say(&ctx.http, IMPORT_TOO_LARGE_MESSAGE)
+                    .await;
+                return;
+            }
+            let rows = match attachment.download().await {
+                Ok(bytes) => parse_import(&bytes),
+                Err(e) => {
+                    warn!("Error downloading import file: {:?}", e);
+                    let _ = msg.channel_id.say(&ctx.http, IMPORT_DOWNLOAD_MESSAGE).await;
+                    return;
+                }
+            };
+            let Some(rows) = rows else {
+                let _ = msg.channel_id.say(&ctx.http, IMPORT_USAGE_MESSAGE).await;
+                return;
+            };
+
+            let user_id = msg.author.id.to_string();
+            let timezone = self.user_timezone(&user_id).await;
+            let locale = self.user_locale(&user_id).await;
+            let mut room = match count_user_reminders(&self.pool, &user_id).await {
+                Ok(pending) => settings.max_reminders_per_user - pending,
+                Err(e) => {
+                    warn!("Error counting reminders, allowing import: {:?}", e);
+                    settings.max_reminders_per_user
+                }
+            };
+            let now = Utc::now();
+            let mut summary = ImportSummary {
+                skipped: rows.len().saturating_sub(MAX_IMPORTED_REMINDERS),
+                ..ImportSummary::default()
+            };
+            for (index, row) in rows.iter().take(MAX_IMPORTED_REMINDERS).enumerate() {
+                let row_number = index + 1;
+                if room <= 0 {
+                    summary
+                        .failed
+                        .push((row_number, reminder_limit_message(prefix)));
+                    continue;
+                }
+                let (trigger_time, message_content, recurrence_interval) =
+                    match validate_import_row(row, timezone, now, &self.config, prefix, &locale) {
+                        Ok(valid) => valid,
+                        Err(reason) => {
+                            summary.failed.push((row_number, reason));
+                            continue;
+                        }
+                    };
                 let reminder = Reminder {
                     id: None,
-                    user_id: msg.author.id.to_string(),
+                    user_id: user_id.clone(),
+                    target_user_id: user_id.clone(),
                     channel_id: msg.channel_id.to_string(),
-                    message_id: msg.id.to_string(),
-                    message_content: text.unwrap_or_else(|| "".to_string()),
+                    message_id: imported_message_id(msg.id, row_number),
+                    message_content,
                     trigger_time,
+                    recurrence_interval,
+                    status: STATUS_PENDING.to_string(),
+                    attempts: 0,
+                    deliver_dm: false,
+                    remaining_occurrences: None,
+                    created_at: None,
+                    target_role_id: None,
+                    priority: PRIORITY_NORMAL,
+                    deleted_at: None,
+                    tag: None,
+                    retry_at: None,
+                    source: Some(SOURCE_TEXT.to_string()),
                 };
-
-                let result = sqlx::query!(
-                    r#"
-                    INSERT INTO reminders (user_id, message_id, message_content, trigger_time, channel_id)
-                    VALUES ($1, $2, $3, $4, $5)
-                    "#,
-                    reminder.user_id,
-                    reminder.message_id,
-                    reminder.message_content,
-                    reminder.trigger_time,
-                    reminder.channel_id
-                )
-                .execute(&self.pool)
-                .await;
-
-                match result {
-                    Ok(_) => {
-                        let _ = msg
-                            .channel_id
-                            .say(&ctx.http, "Reminder set successfully")
-                            .await;
+                match save_reminder(&self.pool, &reminder).await {
+                    Ok(true) => {
+                        summary.imported += 1;
+                        room -= 1;
                     }
+                    Ok(false) => summary
+                        .failed
+                        .push((row_number, ALREADY_SET_MESSAGE.to_string())),
                     Err(e) => {
-                        println!("Error setting reminder: {:?}", e);
+                        error!("Error importing reminder: {:?}", e);
+                        summary
+                            .failed
+                            .push((row_number, INSERT_FAILED_MESSAGE.to_string()));
                     }
                 }
-            } else {
-                let _ = msg.channel_id.say(&ctx.http, "Invalid date format").await;
             }
+            info!(user_id = %msg.author.id, imported = summary.imported, "Imported reminders");
+            let _ = msg
+                .channel_id
+                .say(&ctx.http, import_summary_message(&summary))
+                .await;
+            return;
         }
-    }
 
-    async fn ready(&self, _: Context, ready: Ready) {
-        println!("{} is connected!", ready.user.name);
-    }
-}
-
-#[derive(Debug, FromRow)]
-struct Reminder {
-    id: Option<i32>,
-    user_id: String,
-    message_id: String,
-    message_content: String,
-    trigger_time: NaiveDateTime,
-    channel_id: String,
-}
+        if is_test_command(&msg.content, prefix) {
+            let reminder = test_reminder(
+                msg.author.id.to_string(),
+                msg.channel_id.to_string(),
+                msg.id.to_string(),
+                Utc::now(),
+            );
+            let saved = save_reminder(&self.pool, &reminder).await;
+            if let Err(e) = &saved {
+                error!("Error setting test reminder: {:?}", e);
+            }
+            let reply = not_saved_message(&saved).unwrap_or(TEST_SCHEDULED_MESSAGE);
+            let _ = msg.channel_id.say(&ctx.http, reply).await;
+            return;
+        }
 
-async fn get_due_reminders(pool: &PgPool) -> Result<Vec<Reminder>, sqlx::Error> {
-    let now = chrono::Local::now().naive_local();
-    let reminders = sqlx::query_as!(
-        Reminder,
-        r#"SELECT * FROM reminders WHERE trigger_time < $1"#,
-        now
-    )
-    .fetch_all(pool)
-    .await?;
-    Ok(reminders)
-}
+        if command == "cancel" {
+            if let Some(fragment) = parse_cancel_text_command(&msg.content, prefix) {
+                let user_id = msg.author.id.to_string();
+                let matches =
+                    match find_user_reminders_by_text(&self.pool, &user_id, &fragment).await {
+                        Ok(matches) => matches,
+                        Err(e) => {
+                            error!("Error finding reminders: {:?}", e);
+                            return;
+                        }
+                    };
+                let timezone = self.user_timezone(&user_id).await;
+                let response = match text_matches(matches) {
+                    TextMatches::Nothing => no_text_match_message(&fragment),
+                    TextMatches::One(reminder) => {
+                        match delete_user_reminder(&self.pool, &user_id, *reminder).await {
+                            Ok(Some(reminder)) => reminder_summary(
+                                "Cancelled your reminder set for",
+                                &reminder,
+                                timezone,
+                            ),
+                            Ok(None) => {
+                                "That reminder has already gone out or been cancelled".to_string()
+                            }
+                            Err(e) => {
+                                error!("Error cancelling reminder: {:?}", e);
+                                return;
+                            }
+                        }
+                    }
+                    TextMatches::Several(reminders) => {
+                        several_text_matches_message(&reminders, timezone, prefix)
+                    }
+                };
+                let _ = msg.channel_id.say(&ctx.http, &response).await;
+                return;
+            }
 
-fn parse_reminder_command(message: &str) -> Option<(String, Option<String>)> {
-    let regex = Regex::new(r"!remindme\s+(\S+)(?:\s+(.+))?").unwrap();
+            let Some(index) = parse_cancel_command(&msg.content, prefix) else {
+                let _ = msg
+                    .channel_id
+                    .say(
+                        &ctx.http,
+                        format!(
+                            "Usage: `{p}cancel <number from {p}reminders>` or `{p}cancel text <part of the message>`",
+                            p = prefix
+                        ),
+                    )
+                    .await;
+                return;
+            };
 
-    regex.captures(message).map(|caps| {
-        let date_str = caps.get(1).map_or("", |m| m.as_str()).to_string();
-        let text = caps.get(2).map(|m| m.as_str().to_string());
-        (date_str, text)
-    })
-}
+            let timezone = self.user_timezone(&msg.author.id.to_string()).await;
+            let response =
+                match cancel_reminder_for_user(&self.pool, &msg.author.id.to_string(), index).await
+                {
+                    Ok(Some(reminder)) => reminder_summary(
+                        &format!("Cancelled reminder {} set for", index),
+                        &reminder,
+                        timezone,
+                    ),
+                    Ok(None) => no_such_reminder_message(index, prefix),
+                    Err(e) => {
+                        error!("Error cancelling reminder: {:?}", e);
+                        return;
+                    }
+                };
+            let _ = msg.channel_id.say(&ctx.http, &response).await;
+            return;
+        }
 
-fn parse_date_str(date_str: &str) -> Option<NaiveDateTime> {
-    let datetime_regex = Regex::new(r"^(\d{4})-(\d{2})-(\d{2})-(\d{2})-(\d{2})$").unwrap();
-    let duration_regex = Regex::new(r"^(\d+)([mhdy])$").unwrap();
+        if command == "undo" {
+            let user_id = msg.author.id.to_string();
+            let response = match undo_last_reminder(&self.pool, &user_id).await {
+                Ok(Some(reminder)) => {
+                    let timezone = self.user_timezone(&user_id).await;
+                    reminder_summary("Removed your last reminder, set for", &reminder, timezone)
+                }
+                Ok(None) => "You have no pending reminder to undo".to_string(),
+                Err(e) => {
+                    error!("Error undoing reminder: {:?}", e);
+                    return;
+                }
+            };
+            let _ = msg.channel_id.say(&ctx.http, &response).await;
+            return;
+        }
 
-    if let Some(caps) = datetime_regex.captures(date_str) {
-        let year = caps.get(1)?.as_str().parse::<i32>().ok()?;
-        let month = caps.get(2)?.as_str().parse::<u32>().ok()?;
-        let day = caps.get(3)?.as_str().parse::<u32>().ok()?;
-        let hour = caps.get(4)?.as_str().parse::<u32>().ok()?;
-        let minute = caps.get(5)?.as_str().parse::<u32>().ok()?;
+        if command == "reschedule" {
+            let Some((index, date_str)) = parse_reschedule_command(&msg.content, prefix) else {
+                let _ = msg
+                    .channel_id
+                    .say(
+                        &ctx.http,
+                        format!(
+                            "Usage: `{p}reschedule <number from {p}reminders> <new date>`",
+                            p = prefix
+                        ),
+                    )
+                    .await;
+                return;
+            };
 
-        NaiveDateTime::parse_from_str(
-            &format!("{}-{}-{} {}:{}:00", year, month, day, hour, minute),
-            "%Y-%m-%d %H:%M:%S",
-        )
-        .ok()
-    } else if let Some(caps) = duration_regex.captures(date_str) {
-        let amount = caps.get(1)?.as_str().parse::<i64>().ok()?;
-        let unit = caps.get(2)?.as_str();
+            let user_id = msg.author.id.to_string();
+            let timezone = self.user_timezone(&user_id).await;
+            let locale = self.user_locale(&user_id).await;
+            let now = Utc::now();
+            let new_time = match validate_trigger_time(
+                &date_str,
+                timezone,
+                now,
+                &self.config,
+                prefix,
+                &locale,
+            ) {
+                Ok(new_time) => new_time,
+                Err(response) => {
+                    let _ = msg.channel_id.say(&ctx.http, response).await;
+                    return;
+                }
+            };
+            let reminder = match resolve_user_reminder(&self.pool, &user_id, index).await {
+                Ok(reminder) => reminder,
+                Err(e) => {
+                    error!("Error getting reminder to reschedule: {:?}", e);
+                    return;
+                }
+            };
+            let Some(id) = reminder.and_then(|reminder| reminder.id) else {
+                let _ = msg
+                    .channel_id
+                    .say(&ctx.http, no_such_reminder_message(index, prefix))
+                    .await;
+                return;
+            };
 
-        let duration = match unit {
-            "m" => chrono::Duration::minutes(amount),
-            "h" => chrono::Duration::hours(amount),
-            "d" => chrono::Duration::days(amount),
-            "y" => chrono::Duration::days(amount * 365),
-            _ => return None,
-        };
+            let response = match reschedule_reminder(&self.pool, &user_id, id, new_time).await {
+                Ok(true) => rescheduled_message(index, new_time, timezone, now),
+                Ok(false) => reminder_gone_message(index),
+                Err(e) => {
+                    error!("Error rescheduling reminder: {:?}", e);
+                    return;
+                }
+            };
+            let _ = msg.channel_id.say(&ctx.http, response).await;
+            return;
+        }
 
-        let future_time = Local::now() + duration;
-        Some(future_time.naive_local())
-    } else {
-        None
-    }
-}
+        if command == "edit" {
+            let Some((index, text)) = parse_edit_command(&msg.content, prefix) else {
+                let _ = msg
+                    .channel_id
+                    .say(
+                        &ctx.http,
+                        format!(
+                            "Usage: `{p}edit <number from {p}reminders> <new message>`",
+                            p = prefix
+                        ),
+                    )
+                    .await;
+                return;
+            };
 
-async fn send_reminder(http: Arc<Http>, reminder: Reminder) {
-    let user_id = reminder.user_id.parse::<UserId>().unwrap();
-    let channel_id = reminder.channel_id.parse::<ChannelId>().unwrap();
-    let message_id = reminder.message_id.parse::<MessageId>().unwrap();
+            let (text, truncated) = limit_length(&text, self.config.max_message_length);
+            let user_id = msg.author.id.to_string();
+            let reminder = match resolve_user_reminder(&self.pool, &user_id, index).await {
+                Ok(reminder) => reminder,
+                Err(e) => {
+                    error!("Error getting reminder to edit: {:?}", e);
+                    return;
+                }
+            };
+            let Some(id) = reminder.and_then(|reminder| reminder.id) else {
+                let _ = msg
+                    .channel_id
+                    .say(&ctx.http, no_such_reminder_message(index, prefix))
+                    .await;
+                return;
+            };
 
-    let user = http.get_user(user_id).await.unwrap();
-    let channel = http.get_channel(channel_id).await.unwrap().guild().unwrap();
-    let message = channel.message(http.as_ref(), message_id).await.unwrap();
+            match update_reminder_text(&self.pool, &user_id, id, &text).await {
+                Ok(true) => {}
+                Ok(false) => {
+                    let _ = msg
+                        .channel_id
+                        .say(&ctx.http, reminder_gone_message(index))
+                        .await;
+                    return;
+                }
+                Err(e) => {
+                    error!("Error editing reminder: {:?}", e);
+                    return;
+                }
+            }
+            let _ = msg
+                .channel_id
+                .say(
+                    &ctx.http,
+                    MessageBuilder::new()
+                        .push(format!("Reminder {} updated: ", index))
+                        .push_safe(text)
+                        .build(),
+                )
+                .await;
+            if truncated {
+                let _ = msg
+                    .channel_id
+                    .say(&ctx.http, truncated_message(self.config.max_message_length))
+                    .await;
+            }
+            return;
+        }
 
-    let reminder_response = MessageBuilder::new()
-        .push("Hey ")
-        .mention(&user)
-        .push(", you asked me to remind you about this: ")
-        .push(reminder.message_content)
-        .push(" ")
-        .push("reference message: ")
-        .push(message.link())
-        .build();
+        if command == "snooze" {
+            let Some(trigger_time) = snooze_until(&msg.content, prefix, Utc::now()) else {
+                let _ = msg
+                    .channel_id
+                    .say(
+                        &ctx.http,
+                        format!(
+                            "Usage: `{p}snooze <duration>`, for example `{p}snooze 1h`",
+                            p = prefix
+                        ),
+                    )
+                    .await;
+                return;
+            };
 
-    if let Err(e) = message
-        .channel_id
-        .say(http.as_ref(), &reminder_response)
-        .await
-    {
-        println!("Error sending reminder: {:?}", e);
-    }
-}
+            let user_id = msg.author.id.to_string();
+            let response =
+                match snooze_last_reminder(&self.pool, &user_id, &msg.id.to_string(), trigger_time)
+                    .await
+                {
+                    Ok(true) => {
+                        let timezone = self.user_timezone(&user_id).await;
+                        format!(
+                            "Snoozed until {}",
+                            format_trigger_time(trigger_time, timezone)
+                        )
+                    }
+                    Ok(false) => "You have no fired reminder to snooze".to_string(),
+                    Err(e) => {
+                        error!("Error snoozing reminder: {:?}", e);
+                        return;
+                    }
+                };
+            let _ = msg.channel_id.say(&ctx.http, &response).await;
+            return;
+        }
 
-async fn cleanup_reminders(pool: &PgPool) {
-    let now = chrono::Local::now().naive_local();
-    let result = sqlx::query!(r#"DELETE FROM reminders WHERE trigger_time < $1"#, now)
-        .execute(pool)
-        .await;
+        if command == "timezone" {
+            let Some(tz) = parse_timezone_command(&msg.content, prefix) else {
+                let _ = msg
+                    .channel_id
+                    .say(
+                        &ctx.http,
+                        format!(
+                            "Usage: `{p}timezone <IANA name>`, for example `{p}timezone Europe/Warsaw`",
+                            p = prefix
+                        ),
+                    )
+                    .await;
+                return;
+            };
 
-    match result {
-        Ok(_) => {}
-        Err(e) => {
-            println!("Error cleaning up reminders: {:?}", e);
+            match set_user_timezone(&self.pool, &msg.author.id.to_string(), tz).await {
+                Ok(_) => {
+                    let _ = msg
+                        .channel_id
+                        .say(&ctx.http, format!("Timezone set to {}", tz.name()))
+                        .await;
+                }
+                Err(e) => {
+                    error!("Error setting timezone: {:?}", e);
+                }
+            }
+            return;
         }
-    }
-}
 
-async fn check_reminders_job(pool: PgPool, http: Arc<Http>) {
-    println!("Checking reminders");
-    let reminders = match get_due_reminders(&pool).await {
-        Ok(reminders) => reminders,
-        Err(e) => {
-            println!("Error getting reminders: {:?}", e);
+        if command == "locale" {
+            let Some(locale) = parse_locale_command(&msg.content, prefix)
+                .as_deref()
+                .and_then(supported_locale)
+            else {
+                let _ = msg.channel_id.say(&ctx.http, locale_usage(prefix)).await;
+                return;
+            };
+
+            match set_user_locale(&self.pool, &msg.author.id.to_string(), locale).await {
+                Ok(_) => {
+                    let _ = msg.channel_id.say(&ctx.http, t(locale, "locale_set")).await;
+                }
+                Err(e) => {
+                    error!("Error setting locale: {:?}", e);
+                }
+            }
             return;
         }
-    };
 
-    for reminder in reminders {
-        send_reminder(http.clone(), reminder).await;
-    }
-}
+        if let Some(command) = parse_anchored_command(&msg.content, prefix) {
+            let AnchoredCommand {
+                index,
+                offset,
+                text,
+            } = command;
+            if self.on_cooldown(msg.author.id).await {
+                let _ = msg.channel_id.say(&ctx.http, COOLDOWN_MESSAGE).await;
+                return;
+            }
+            let user_id = msg.author.id.to_string();
+            let anchor = match resolve_user_reminder(&self.pool, &user_id, index).await {
+                Ok(Some(anchor)) => anchor,
+                Ok(None) => {
+                    let _ = msg
+                        .channel_id
+                        .say(&ctx.http, no_such_reminder_message(index, prefix))
+                        .await;
+                    return;
+                }
+                Err(e) => {
+                    error!("Error resolving reminder: {:?}", e);
+                    return;
+                }
+            };
+            let Some(trigger_time) = anchored_trigger_time(anchor.trigger_time, &offset) else {
+                let _ = msg.channel_id.say(&ctx.http, anchored_usage(prefix)).await;
+                return;
+            };
+            let now = Utc::now();
+            if beyond_horizon(trigger_time, now, self.config.max_horizon_years) {
+                let _ = msg
+                    .channel_id
+                    .say(&ctx.http, horizon_message(self.config.max_horizon_years))
+                    .await;
+                return;
+            }
+            if self
+                .at_reminder_limit(&user_id, settings.max_reminders_per_user)
+                .await
+            {
+                let _ = msg
+                    .channel_id
+                    .say(&ctx.http, reminder_limit_message(prefix))
+                    .await;
+                return;
+            }
 
-async fn cleanup_reminders_job(pool: PgPool) {
-    println!("Cleaning up reminders");
-    cleanup_reminders(&pool).await;
-}
+            let timezone = self.user_timezone(&user_id).await;
+            let locale = self.user_locale(&user_id).await;
+            let (message_content, truncated) =
+                limit_length(&text.unwrap_or_default(), self.config.max_message_length);
+            info!(
+                user_id = %msg.author.id,
+                anchor_id = ?anchor.id,
+                %trigger_time,
+                "Setting anchored reminder"
+            );
+            let reminder = Reminder {
+                id: None,
+                target_user_id: user_id.clone(),
+                user_id,
+                channel_id: msg.channel_id.to_string(),
+                message_id: msg.id.to_string(),
+                message_content,
+                trigger_time,
+                recurrence_interval: None,
+                status: STATUS_PENDING.to_string(),
+                attempts: 0,
+                deliver_dm: false,
+                remaining_occurrences: None,
+                created_at: None,
+                target_role_id: None,
+                priority: PRIORITY_NORMAL,
+                deleted_at: None,
+                tag: None,
+                retry_at: None,
+                source: Some(SOURCE_TEXT.to_string()),
+            };
 
-#[tokio::main]
-async fn main() {
-    dotenv::dotenv().ok();
-    let token = env::var("DISCORD_TOKEN").expect("Expected a token in the environment");
-    let db_url = env::var("DATABASE_URL").expect("Expected a database URL in the environment");
+            let saved = save_reminder(&self.pool, &reminder).await;
+            if let Err(e) = &saved {
+                error!("Error setting reminder: {:?}", e);
+            }
+            let Confirmation { reaction, reply } = confirmation(
+                &saved,
+                self.config.confirm_with_reaction,
+                reminder_set_message(trigger_time, timezone, now, &locale),
+            );
+            if let Some(reaction) = reaction {
+                let _ = msg.react(&ctx.http, reaction).await;
+            }
+            if let Some(reply) = reply {
+                let _ = msg.channel_id.say(&ctx.http, reply).await;
+            }
+            if matches!(saved, Ok(true)) && truncated {
+                let _ = msg
+                    .channel_id
+                    .say(&ctx.http, truncated_message(self.config.max_message_length))
+                    .await;
+            }
+            return;
+        }
 
-    let pool = PgPool::connect(&db_url)
-        .await
-        .expect("Failed to connect to database");
+        if let Some(command) = parse_reminder_command(&msg.content, prefix) {
+            let ReminderCommand {
+                target_user_id,
+                target_role_id,
+                channel_id,
+                date_str,
+                text,
+                recurring,
+                repeat_count,
+                deliver_dm,
+                high_priority,
+                tag,
+            } = command;
+            if date_str.is_empty() {
+                let _ = msg.channel_id.say(&ctx.http, remindme_usage(prefix)).await;
+                return;
+            }
+            if self.on_cooldown(msg.author.id).await {
+                let _ = msg.channel_id.say(&ctx.http, COOLDOWN_MESSAGE).await;
+                return;
+            }
 
-    sqlx::migrate!()
-        .run(&pool)
-        .await
-        .expect("Failed to run migrations");
+            let timezone = match get_user_timezone(&self.pool, &msg.author.id.to_string()).await {
+                Ok(timezone) => timezone,
+                Err(e) => {
+                    warn!("Error getting timezone, using server timezone: {:?}", e);
+                    None
+                }
+            };
+            let locale = self.user_locale(&msg.author.id.to_string()).await;
 
-    let mut scheduler = AsyncScheduler::new();
+            let now = Utc::now();
+            let trigger_time = if recurring {
+                next_recurrence_after(&date_str, now, now, timezone)
+                    .ok_or(DateParseError::UnknownFormat)
+            } else {
+                parse_date_str(
+                    &date_str,
+                    timezone,
+                    now,
+                    self.config.default_time,
+                    &self.config.holidays,
+                )
+            };
+            let trigger_time = match trigger_time {
+                Ok(trigger_time) => trigger_time,
+                Err(e) => {
+                    let response = match cron_expression(&date_str) {
+                        Some(expression) => invalid_cron_message(expression, prefix),
+                        None => date_error_message(&e, &date_str, prefix, &locale),
+                    };
+                    let _ = msg.channel_id.say(&ctx.http, response).await;
+                    return;
+                }
+            };
+            if beyond_horizon(trigger_time, now, self.config.max_horizon_years) {
+                let _ = msg
+                    .channel_id
+                    .say(&ctx.http, horizon_message(self.config.max_horizon_years))
+                    .await;
+                return;
+            }
 
-    let intents = GatewayIntents::GUILD_MESSAGES
-        | GatewayIntents::DIRECT_MESSAGES
-        | GatewayIntents::MESSAGE_CONTENT;
+            // Bots can't act on reminders, so a bot mention just reminds the author.
+            let target_user_id = target_user_id
+                .filter(|target| {
+                    !msg.mentions
+                        .iter()
+                        .any(|user| user.bot && user.id.to_string() == *target)
+                })
+                .unwrap_or_else(|| msg.author.id.to_string());
 
-    let bot = Handler { pool: pool.clone() };
-    let http = Arc::new(Http::new(&token));
-    let pool2 = pool.clone();
+            let Some(channel_id) = reminder_channel_id(channel_id.as_deref(), msg.channel_id)
+            else {
+                let _ = msg
+                    .channel_id
+                    .say(&ctx.http, CHANNEL_UNAVAILABLE_MESSAGE)
+                    .await;
+                return;
+            };
+            if channel_id != msg.channel_id {
+                let bot_id = ctx.cache.current_user().id;
+                match can_post_in(&ctx.http, msg.guild_id, channel_id, bot_id).await {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        let _ = msg
+                            .channel_id
+                            .say(&ctx.http, CHANNEL_UNAVAILABLE_MESSAGE)
+                            .await;
+                        return;
+                    }
+                    Err(e) => {
+                        warn!("Error checking channel permissions: {:?}", e);
+                        let _ = msg
+                            .channel_id
+                            .say(&ctx.http, CHANNEL_UNAVAILABLE_MESSAGE)
+                            .await;
+                        return;
+                    }
+                }
+            }
 
-    scheduler.every(1.minutes()).run(move || {
-        let pool = pool2.clone();
-        let http = http.clone();
+            if let Some(role_id) = &target_role_id {
+                if deliver_dm {
+                    let _ = msg.channel_id.say(&ctx.http, ROLE_DM_MESSAGE).await;
+                    return;
+                }
+                let bot_id = ctx.cache.current_user().id;
+                let allowed = match role_id.parse::<RoleId>() {
+                    Ok(role_id) => {
+                        can_mention_role(&ctx.http, msg.guild_id, role_id, msg.author.id, bot_id)
+                            .await
+                            .unwrap_or_else(|e| {
+                                warn!("Error checking role permissions: {:?}", e);
+                                false
+                            })
+                    }
+                    Err(_) => false,
+                };
+                if !allowed {
+                    let _ = msg
+                        .channel_id
+                        .say(&ctx.http, ROLE_UNAVAILABLE_MESSAGE)
+                        .await;
+                    return;
+                }
+            }
 
-        async move {
-            check_reminders_job(pool, http).await;
-        }
-    });
+            if self
+                .at_reminder_limit(&msg.author.id.to_string(), settings.max_reminders_per_user)
+                .await
+            {
+                let _ = msg
+                    .channel_id
+                    .say(&ctx.http, reminder_limit_message(prefix))
+                    .await;
+                return;
+            }
+            // Mentioned channels were checked above and DMs fall back to this
+            // one, so only the channel it was set in is left to check.
+            let postable = channel_id != msg.channel_id
+                || can_post_where_set(&ctx, msg.guild_id, channel_id).await;
 
-    scheduler.every(1.minutes()).run(move || {
-        let pool = pool.clone();
-        async move {
-            cleanup_reminders_job(pool).await;
-        }
-    });
+            let legacy_minutes = uses_legacy_minutes(&date_str);
+            let (message_content, truncated) =
+                limit_length(&text.unwrap_or_default(), self.config.max_message_length);
+            info!(user_id = %msg.author.id, %target_user_id, %trigger_time, "Setting reminder");
+            let reminder = Reminder {
+                id: None,
+                user_id: msg.author.id.to_string(),
+                target_user_id,
+                channel_id: channel_id.to_string(),
+                message_id: msg.id.to_string(),
+                message_content,
+                trigger_time,
+                recurrence_interval: recurring.then_some(date_str),
+                status: STATUS_PENDING.to_string(),
+                attempts: 0,
+                deliver_dm,
+                remaining_occurrences: repeat_count,
+                created_at: None,
+                target_role_id,
+                priority: if high_priority {
+                    PRIORITY_HIGH
+                } else {
+                    PRIORITY_NORMAL
+                },
+                deleted_at: None,
+                tag,
+                retry_at: None,
+                source: Some(SOURCE_TEXT.to_string()),
+            };
 
-    tokio::spawn(async move {
-        loop {
-            scheduler.run_pending().await;
-            tokio::time::sleep(Duration::from_millis(100)).await;
+            let saved = save_reminder(&self.pool, &reminder).await;
+            if let Err(e) = &saved {
+                error!("Error setting reminder: {:?}", e);
+            }
+            let Confirmation { reaction, reply } = confirmation(
+                &saved,
+                self.config.confirm_with_reaction,
+                reminder_set_message(trigger_time, timezone, Utc::now(), &locale),
+            );
+            if let Some(reaction) = reaction {
+                let _ = msg.react(&ctx.http, reaction).await;
+            }
+            if let Some(reply) = reply {
+                let _ = msg.channel_id.say(&ctx.http, reply).await;
+            }
+            let inserted = matches!(saved, Ok(true));
+            if inserted && !postable {
+                // The channel is closed to the bot, so the author hears by DM.
+                let warning = CreateMessage::new().content(NO_SEND_PERMISSION_MESSAGE);
+                if let Err(e) = msg.author.direct_message(&ctx.http, warning).await {
+                    warn!("Error warning about channel permissions: {:?}", e);
+                }
+            }
+            if inserted && legacy_minutes {
+                let _ = msg.channel_id.say(&ctx.http, LEGACY_MINUTES_MESSAGE).await;
+            }
+            if inserted && truncated {
+                let _ = msg
+                    .channel_id
+                    .say(&ctx.http, truncated_message(self.config.max_message_length))
+                    .await;
+            }
         }
-    });
+    }
 
-    let mut client = Client::builder(&token, intents)
-        .event_handler(bot)
-        .await
-        .expect("Error creating client");
+    async fn ready(&self, ctx: Context, ready: Ready) {
+        info!("{} is connected!", ready.user.name);
+
+        let command = CreateCommand::new("remindme")
+            .description("Remind you about something in the future")
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "time",
+                    "A date like 2021-01-01-12-00 or a duration like 1d",
+                )
+                .required(true),
+            )
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::String,
+                "message",
+                "What to remind you about",
+            ));
+
+        if let Err(e) = Command::create_global_command(&ctx.http, command).await {
+            error!("Error registering slash command: {:?}", e);
+        }
+    }
+
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        let command = match interaction {
+            Interaction::Command(command) => command,
+            Interaction::Component(component) => {
+                self.turn_list_page(&ctx, &component).await;
+                return;
+            }
+            _ => return,
+        };
+        if command.data.name != "remindme" {
+            return;
+        }
+
+        let Some((date_str, text)) = parse_remindme_options(&command.data.options()) else {
+            return;
+        };
+
+        let timezone = match get_user_timezone(&self.pool, &command.user.id.to_string()).await {
+            Ok(timezone) => timezone,
+            Err(e) => {
+                warn!("Error getting timezone, using server timezone: {:?}", e);
+                None
+            }
+        };
+        let locale = self.user_locale(&command.user.id.to_string()).await;
+        let settings = self.server_settings(command.guild_id).await;
+
+        let now = Utc::now();
+        let trigger_time = match parse_date_str(
+            &date_str,
+            timezone,
+            now,
+            self.config.default_time,
+            &self.config.holidays,
+        ) {
+            Ok(trigger_time) => trigger_time,
+            Err(e) => {
+                let response = CreateInteractionResponseMessage::new()
+                    .content(date_error_message(&e, &date_str, &settings.prefix, &locale))
+                    .ephemeral(true);
+                let _ = command
+                    .create_response(&ctx.http, CreateInteractionResponse::Message(response))
+                    .await;
+                return;
+            }
+        };
+        let rejection = if beyond_horizon(trigger_time, now, self.config.max_horizon_years) {
+            Some(horizon_message(self.config.max_horizon_years))
+        } else if self
+            .at_reminder_limit(
+                &command.user.id.to_string(),
+                settings.max_reminders_per_user,
+            )
+            .await
+        {
+            Some(reminder_limit_message(&settings.prefix))
+        } else {
+            None
+        };
+        if let Some(rejection) = rejection {
+            let response = CreateInteractionResponseMessage::new()
+                .content(rejection)
+                .ephemeral(true);
+            let _ = command
+                .create_response(&ctx.http, CreateInteractionResponse::Message(response))
+                .await;
+            return;
+        }
+        info!(user_id = %command.user.id, %trigger_time, "Setting reminder");
+
+        let (message_content, truncated) =
+            limit_length(&text.unwrap_or_default(), self.config.max_message_length);
+        let mut content = reminder_set_message(trigger_time, timezone, Utc::now(), &locale);
+        if !can_post_where_set(&ctx, command.guild_id, command.channel_id).await {
+            content = format!("{}\n{}", content, NO_SEND_PERMISSION_MESSAGE);
+        }
+        if truncated {
+            content = format!(
+                "{}\n{}",
+                content,
+                truncated_message(self.config.max_message_length)
+            );
+        }
+        // Slash commands have no triggering message, so the bot's own reply
+        // serves as the reference message for the reminder.
+        let response = CreateInteractionResponseMessage::new().content(content);
+        if let Err(e) = command
+            .create_response(&ctx.http, CreateInteractionResponse::Message(response))
+            .await
+        {
+            error!("Error responding to slash command: {:?}", e);
+            return;
+        }
+        let response_message = match command.get_response(&ctx.http).await {
+            Ok(message) => message,
+            Err(e) => {
+                error!("Error fetching slash command response: {:?}", e);
+                return;
+            }
+        };
+
+        let reminder = Reminder {
+            id: None,
+            user_id: command.user.id.to_string(),
+            target_user_id: command.user.id.to_string(),
+            channel_id: command.channel_id.to_string(),
+            message_id: response_message.id.to_string(),
+            message_content,
+            trigger_time,
+            recurrence_interval: None,
+            status: STATUS_PENDING.to_string(),
+            attempts: 0,
+            deliver_dm: false,
+            remaining_occurrences: None,
+            created_at: None,
+            target_role_id: None,
+            priority: PRIORITY_NORMAL,
+            deleted_at: None,
+            tag: None,
+            retry_at: None,
+            source: Some(SOURCE_SLASH.to_string()),
+        };
+
+        let saved = save_reminder(&self.pool, &reminder).await;
+        if let Err(e) = &saved {
+            error!("Error setting reminder: {:?}", e);
+        }
+        if let Some(message) = not_saved_message(&saved) {
+            // The response already said the reminder was set, so take that back.
+            let response = EditInteractionResponse::new().content(message);
+            if let Err(e) = command.edit_response(&ctx.http, response).await {
+                error!("Error reporting failed slash command: {:?}", e);
+            }
+        }
+    }
+}
+
+/// `insert_reminder`, counting the reminders that were actually set.
+async fn save_reminder(pool: &PgPool, reminder: &Reminder) -> Result<bool, sqlx::Error> {
+    let inserted = insert_reminder(pool, reminder).await?;
+    if inserted {
+        COUNTERS.created.fetch_add(1, Ordering::Relaxed);
+    }
+    Ok(inserted)
+}
+
+/// Where the reminder fires, the channel it was set in unless another was
+/// mentioned. `None` for a mention that isn't a valid channel id.
+fn reminder_channel_id(mentioned: Option<&str>, current: ChannelId) -> Option<ChannelId> {
+    mentioned.map_or(Some(current), |id| id.parse().ok())
+}
+
+/// Never answer bots whatever the command is, and never ourselves even if
+/// the account isn't flagged as a bot, so a reply quoting a command can't
+/// set off another one.
+fn is_ignored_author(author: &User, bot_id: UserId) -> bool {
+    author.bot || author.id == bot_id
+}
+
+/// Only channels in the same server the bot can send messages to, so a
+/// reminder can't be aimed at a server the author isn't in.
+async fn can_post_in(
+    http: &Http,
+    guild_id: Option<GuildId>,
+    channel_id: ChannelId,
+    bot_id: UserId,
+) -> Result<bool, serenity::Error> {
+    let Some(guild_id) = guild_id else {
+        return Ok(false);
+    };
+    let channel = match channel_id.to_channel(http).await {
+        Ok(Channel::Guild(channel)) if channel.guild_id == guild_id => channel,
+        Ok(_) => return Ok(false),
+        Err(e) if is_not_found(&e) => return Ok(false),
+        Err(e) => return Err(e),
+    };
+    let guild = guild_id.to_partial_guild(http).await?;
+    let member = guild_id.member(http, bot_id).await?;
+    Ok(can_send_messages(
+        guild.user_permissions_in(&channel, &member),
+    ))
+}
+
+/// Roles in the same server that both the author and the bot may ping, so
+/// the bot can't be used to ping a role the author couldn't.
+async fn can_mention_role(
+    http: &Http,
+    guild_id: Option<GuildId>,
+    role_id: RoleId,
+    author_id: UserId,
+    bot_id: UserId,
+) -> Result<bool, serenity::Error> {
+    let Some(guild_id) = guild_id else {
+        return Ok(false);
+    };
+    let guild = guild_id.to_partial_guild(http).await?;
+    let Some(role) = guild.roles.get(&role_id) else {
+        return Ok(false);
+    };
+    for user_id in [author_id, bot_id] {
+        let member = guild_id.member(http, user_id).await?;
+        if !may_mention_role(role.mentionable, guild.member_permissions(&member)) {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+fn may_mention_role(mentionable: bool, permissions: Permissions) -> bool {
+    mentionable || permissions.mention_everyone()
+}
+
+fn can_send_messages(permissions: Permissions) -> bool {
+    permissions.view_channel() && permissions.send_messages()
+}
+
+/// Whether a reminder will get through in the channel it was set in, which
+/// can be closed to the bot even though it sees the command. Assumed when
+/// the permissions can't be checked, and always true outside servers.
+async fn can_post_where_set(
+    ctx: &Context,
+    guild_id: Option<GuildId>,
+    channel_id: ChannelId,
+) -> bool {
+    if guild_id.is_none() {
+        return true;
+    }
+    let bot_id = ctx.cache.current_user().id;
+    match can_post_in(&ctx.http, guild_id, channel_id, bot_id).await {
+        Ok(postable) => postable,
+        Err(e) => {
+            warn!("Error checking channel permissions: {:?}", e);
+            true
+        }
+    }
+}
+
+/// The author's permissions in the server, none in DMs.
+async fn author_permissions(http: &Http, msg: &Message) -> Result<Permissions, serenity::Error> {
+    let Some(guild_id) = msg.guild_id else {
+        return Ok(Permissions::empty());
+    };
+    let guild = guild_id.to_partial_guild(http).await?;
+    let member = guild_id.member(http, msg.author.id).await?;
+    Ok(guild.member_permissions(&member))
+}
+
+fn format_reminder_stats(stats: &ReminderStats) -> String {
+    let time_or_none = |time: Option<DateTime<Utc>>| {
+        time.map_or("none".to_string(), |time| format_trigger_time(time, None))
+    };
+    let by_source = if stats.by_source.is_empty() {
+        "none".to_string()
+    } else {
+        stats
+            .by_source
+            .iter()
+            .map(|(source, count)| format!("{} {}", source, count))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    format!(
+        "Pending reminders: {}\nOldest: {}\nSoonest: {}\nBy source: {}",
+        stats.pending,
+        time_or_none(stats.oldest),
+        time_or_none(stats.soonest),
+        by_source
+    )
+}
+
+fn digest_set_message(enabled: bool, digest_hour: u32) -> String {
+    if enabled {
+        format!(
+            "You'll get a DM around {:02}:00 your time each day listing your reminders for the next 24 hours",
+            digest_hour
+        )
+    } else {
+        "Daily digest turned off".to_string()
+    }
+}
+
+/// How far ahead `!upcoming` looks at most, a week.
+const MAX_UPCOMING_HOURS: u32 = 168;
+
+fn upcoming_usage(prefix: &str) -> String {
+    format!(
+        "Usage: `{p}upcoming <hours>`, from 1 to {}, for example `{p}upcoming 24`",
+        MAX_UPCOMING_HOURS,
+        p = prefix
+    )
+}
+
+fn format_upcoming(hours: u32, counts: &[(DateTime<Utc>, i64)]) -> String {
+    let total: i64 = counts.iter().map(|(_, count)| count).sum();
+    if total == 0 {
+        return format!("No reminders due in the next {} hours", hours);
+    }
+    let mut lines = vec![format!(
+        "{} reminders due in the next {} hours:",
+        total, hours
+    )];
+    lines.extend(
+        counts
+            .iter()
+            .map(|(hour, count)| format!("{}: {}", format_trigger_time(*hour, None), count)),
+    );
+    lines.join("\n")
+}
+
+enum TextMatches {
+    Nothing,
+    One(Box<Reminder>),
+    Several(Vec<Reminder>),
+}
+
+/// Only an unambiguous match is cancelled.
+fn text_matches(mut reminders: Vec<Reminder>) -> TextMatches {
+    match reminders.len() {
+        0 => TextMatches::Nothing,
+        1 => TextMatches::One(Box::new(reminders.remove(0))),
+        _ => TextMatches::Several(reminders),
+    }
+}
+
+fn no_text_match_message(fragment: &str) -> String {
+    MessageBuilder::new()
+        .push("None of your pending reminders mention \"")
+        .push_safe(truncate(fragment, LISTED_CONTENT_LENGTH))
+        .push("\"")
+        .build()
+}
+
+fn several_text_matches_message(
+    reminders: &[Reminder],
+    timezone: Option<Tz>,
+    prefix: &str,
+) -> String {
+    let mut builder = MessageBuilder::new();
+    builder.push_line(format!(
+        "{} reminders match, use a longer part of the message or `{p}cancel <number from {p}reminders>`:",
+        reminders.len(),
+        p = prefix
+    ));
+    for reminder in reminders.iter().take(REMINDERS_PER_PAGE) {
+        builder
+            .push(format!(
+                "- {} - ",
+                format_trigger_time(reminder.trigger_time, timezone)
+            ))
+            .push_line_safe(truncate(&reminder.message_content, LISTED_CONTENT_LENGTH));
+    }
+    builder.build()
+}
+
+fn cancelled_all_message(count: u64) -> String {
+    match count {
+        0 => "You have no pending reminders to cancel".to_string(),
+        1 => "Cancelled your 1 pending reminder".to_string(),
+        count => format!("Cancelled all {} of your pending reminders", count),
+    }
+}
+
+const REMINDERS_PER_PAGE: usize = 10;
+/// How long a paged list's buttons keep working after they were last used.
+const LIST_PAGES_TTL: Duration = Duration::from_secs(10 * 60);
+const PREVIOUS_PAGE_ID: &str = "reminders_previous_page";
+const NEXT_PAGE_ID: &str = "reminders_next_page";
+
+struct ReminderListPages {
+    user_id: UserId,
+    /// The tag the list was filtered by, kept when turning pages.
+    tag: Option<String>,
+    page: usize,
+    last_used: Instant,
+}
+
+/// The page number, clamped to the last page, and the items on it.
+fn page_of<T>(items: &[T], page: usize) -> (usize, &[T]) {
+    let page = page.min(page_count(items.len()) - 1);
+    let start = page * REMINDERS_PER_PAGE;
+    let end = items.len().min(start + REMINDERS_PER_PAGE);
+    (page, &items[start..end])
+}
+
+fn page_count(items: usize) -> usize {
+    items.div_ceil(REMINDERS_PER_PAGE).max(1)
+}
+
+fn page_buttons(page: usize, pages: usize) -> Vec<CreateActionRow> {
+    vec![CreateActionRow::Buttons(vec![
+        CreateButton::new(PREVIOUS_PAGE_ID)
+            .label("Prev")
+            .style(ButtonStyle::Secondary)
+            .disabled(page == 0),
+        CreateButton::new(NEXT_PAGE_ID)
+            .label("Next")
+            .style(ButtonStyle::Secondary)
+            .disabled(page + 1 >= pages),
+    ])]
+}
+const LISTED_CONTENT_LENGTH: usize = 50;
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        text.to_string()
+    } else {
+        let truncated: String = text.chars().take(max_chars).collect();
+        format!("{}...", truncated)
+    }
+}
+
+/// Cuts `text` to at most `max_chars` including the ellipsis, and says
+/// whether anything was cut.
+fn limit_length(text: &str, max_chars: usize) -> (String, bool) {
+    if text.chars().count() <= max_chars {
+        (text.to_string(), false)
+    } else {
+        (truncate(text, max_chars.saturating_sub(3)), true)
+    }
+}
+
+fn truncated_message(max_chars: usize) -> String {
+    format!(
+        "Your message was too long, so it was cut to {} characters",
+        max_chars
+    )
+}
+
+/// The settings in force in a server, its own where it has any.
+#[derive(Debug, PartialEq)]
+struct ServerSettings {
+    prefix: String,
+    max_reminders_per_user: i64,
+}
+
+fn resolve_settings(config: &Config, overrides: GuildSettings) -> ServerSettings {
+    ServerSettings {
+        prefix: overrides.prefix.unwrap_or_else(|| config.prefix.clone()),
+        max_reminders_per_user: overrides
+            .max_reminders_per_user
+            .unwrap_or(config.max_reminders_per_user),
+    }
+}
+
+fn format_server_settings(settings: &ServerSettings) -> String {
+    MessageBuilder::new()
+        .push("Commands here start with ")
+        .push_mono_safe(&settings.prefix)
+        .push(format!(
+            " and everyone can have up to {} pending reminders. ",
+            settings.max_reminders_per_user
+        ))
+        .push("Change them with ")
+        .push_mono_safe(format!("{}settings prefix <prefix>", settings.prefix))
+        .push(" or ")
+        .push_mono_safe(format!("{}settings limit <number>", settings.prefix))
+        .push(", `default` goes back to the bot's own")
+        .build()
+}
+
+fn settings_usage(prefix: &str) -> String {
+    format!(
+        "Usage: `{p}settings`, `{p}settings prefix <prefix>` with at most {} characters, or `{p}settings limit <number>`, `default` in place of a value to go back to the bot's own",
+        MAX_PREFIX_LENGTH,
+        p = prefix
+    )
+}
+
+fn no_such_reminder_message(index: usize, prefix: &str) -> String {
+    format!(
+        "You have no reminder number {}. Use `{}reminders` to see your reminders.",
+        index, prefix
+    )
+}
+
+fn rescheduled_message(
+    index: usize,
+    new_time: DateTime<Utc>,
+    timezone: Option<Tz>,
+    now: DateTime<Utc>,
+) -> String {
+    format!(
+        "Reminder {} moved to {} ({})",
+        index,
+        format_trigger_time(new_time, timezone),
+        format_relative(new_time - now)
+    )
+}
+
+/// When a reminder fires or is cancelled between being looked up and changed.
+fn reminder_gone_message(index: usize) -> String {
+    format!("Reminder {} has already gone out or been cancelled", index)
+}
+
+/// "<intro> <time>: <content>", with the content escaped so a reminder
+/// can't ping anyone when it's echoed back.
+fn reminder_summary(intro: &str, reminder: &Reminder, timezone: Option<Tz>) -> String {
+    MessageBuilder::new()
+        .push(format!(
+            "{} {}: ",
+            intro,
+            format_trigger_time(reminder.trigger_time, timezone)
+        ))
+        .push_safe(truncate(&reminder.message_content, LISTED_CONTENT_LENGTH))
+        .build()
+}
+
+/// Numbered by place among all of the user's reminders, even when filtered
+/// by tag, so the numbers work with `cancel` and `edit`.
+fn format_reminder_list(
+    reminders: &[Reminder],
+    tag: Option<&str>,
+    timezone: Option<Tz>,
+    page: usize,
+    locale: &str,
+) -> String {
+    let listed = tagged_reminders(reminders, tag);
+    if listed.is_empty() {
+        return match tag {
+            Some(tag) => format!("You have no pending reminders tagged #{}", tag),
+            None => t(locale, "no_reminders").to_string(),
+        };
+    }
+
+    let (page, on_page) = page_of(&listed, page);
+    let mut builder = MessageBuilder::new();
+    builder.push_line(match tag {
+        Some(tag) => format!("Your pending reminders tagged #{}:", tag),
+        None => "Your pending reminders:".to_string(),
+    });
+    for (number, reminder) in on_page {
+        builder
+            .push(format!(
+                "{}. {}{} - ",
+                number,
+                format_trigger_time(reminder.trigger_time, timezone),
+                recurrence_label(reminder)
+            ))
+            .push_safe(truncate(&reminder.message_content, LISTED_CONTENT_LENGTH))
+            .push_line(
+                reminder
+                    .tag
+                    .as_ref()
+                    .map_or(String::new(), |tag| format!(" #{}", tag)),
+            );
+    }
+    let pages = page_count(listed.len());
+    if pages > 1 {
+        builder.push_line(format!("Page {} of {}", page + 1, pages));
+    }
+    builder.build()
+}
+
+/// The reminders tagged `tag`, or all of them without one, each with its
+/// number in the full list.
+fn tagged_reminders<'a>(
+    reminders: &'a [Reminder],
+    tag: Option<&str>,
+) -> Vec<(usize, &'a Reminder)> {
+    reminders
+        .iter()
+        .enumerate()
+        .filter(|(_, reminder)| tag.is_none_or(|tag| reminder.tag.as_deref() == Some(tag)))
+        .map(|(index, reminder)| (index + 1, reminder))
+        .collect()
+}
+
+fn recurrence_label(reminder: &Reminder) -> String {
+    let Some(interval) = &reminder.recurrence_interval else {
+        return String::new();
+    };
+    let rule = match cron_expression(interval) {
+        Some(expression) => format!("cron {}", expression),
+        None => format!("every {}", interval),
+    };
+    match reminder.remaining_occurrences {
+        Some(remaining) => format!(" ({}, {} left)", rule, remaining),
+        None => format!(" ({})", rule),
+    }
+}
+
+/// In the user's timezone when they've set one, in UTC otherwise.
+fn format_trigger_time(trigger_time: DateTime<Utc>, timezone: Option<Tz>) -> String {
+    match timezone {
+        Some(tz) => trigger_time
+            .with_timezone(&tz)
+            .format("%Y-%m-%d %H:%M %Z")
+            .to_string(),
+        None => trigger_time.format("%Y-%m-%d %H:%M UTC").to_string(),
+    }
+}
+
+fn locale_usage(prefix: &str) -> String {
+    format!(
+        "Usage: `{p}locale <language>`, one of {}, for example `{p}locale pl`",
+        LOCALES.join(", "),
+        p = prefix
+    )
+}
+
+fn invalid_cron_message(expression: &str, prefix: &str) -> String {
+    MessageBuilder::new()
+        .push_mono_safe(expression)
+        .push(" isn't a cron expression that ever fires. ")
+        .push(format!(
+            "It needs five fields, minute, hour, day of month, month and day of week with 0 for Sunday, like `{p}remindme cron \"0 9 * * 1-5\" standup` for 9:00 on weekdays",
+            p = prefix
+        ))
+        .build()
+}
+
+fn remindme_usage(prefix: &str) -> String {
+    format!(
+        "Usage: `{p}remindme DATE [OPTIONAL TEXT]`, for example `{p}remindme 1d water the plants`",
+        p = prefix
+    )
+}
+
+fn anchored_usage(prefix: &str) -> String {
+    format!(
+        "Usage: `{p}remindme after <number from {p}reminders> +DURATION [OPTIONAL TEXT]`, for example `{p}remindme after 1 +30m stretch`",
+        p = prefix
+    )
+}
+
+fn reminder_set_message(
+    trigger_time: DateTime<Utc>,
+    timezone: Option<Tz>,
+    now: DateTime<Utc>,
+    locale: &str,
+) -> String {
+    format!(
+        "{} {} ({})",
+        t(locale, "reminder_set"),
+        format_trigger_time(trigger_time, timezone),
+        format_relative(trigger_time - now)
+    )
+}
+
+fn whenis_usage(prefix: &str) -> String {
+    format!(
+        "Usage: `{p}whenis DATE`, for example `{p}whenis next friday 9am`",
+        p = prefix
+    )
+}
+
+/// What `!whenis` replies, the time a reminder would be set for.
+fn resolved_time_message(
+    trigger_time: DateTime<Utc>,
+    timezone: Option<Tz>,
+    now: DateTime<Utc>,
+) -> String {
+    format!(
+        "That's {} ({}), nothing was saved",
+        format_trigger_time(trigger_time, timezone),
+        format_relative(trigger_time - now)
+    )
+}
+
+fn format_relative(duration: chrono::Duration) -> String {
+    let plural = |amount: i64, unit: &str| {
+        if amount == 1 {
+            format!("in about 1 {}", unit)
+        } else {
+            format!("in about {} {}s", amount, unit)
+        }
+    };
+
+    // Round rather than truncate, otherwise `1d` set a moment ago reads as "23 hours".
+    let minutes = (duration.num_seconds() + 30) / 60;
+    let hours = (minutes + 30) / 60;
+    let days = (hours + 12) / 24;
+
+    if minutes < 1 {
+        "in less than a minute".to_string()
+    } else if minutes < 60 {
+        plural(minutes, "minute")
+    } else if hours < 24 {
+        plural(hours, "hour")
+    } else {
+        plural(days, "day")
+    }
+}
+
+const INSERT_FAILED_MESSAGE: &str = "I couldn't save that reminder, please try again later";
+const LEGACY_MINUTES_MESSAGE: &str =
+    "Tip: `m` still means minutes, but write `min` to be clear, or `mo` for months";
+const COOLDOWN_MESSAGE: &str = "Please wait before setting another reminder";
+const PAST_DATE_MESSAGE: &str = "That time is in the past";
+const OVERFLOW_MESSAGE: &str = "That duration is too long to schedule";
+const ALREADY_SET_MESSAGE: &str = "Already set";
+const CHANNEL_UNAVAILABLE_MESSAGE: &str = "I can't post reminders in that channel";
+const NO_SEND_PERMISSION_MESSAGE: &str = "Heads up: I can't send messages in the channel you set this reminder in, so it won't get through unless I'm given permission before it fires";
+const ROLE_UNAVAILABLE_MESSAGE: &str =
+    "I can't ping that role, it has to be mentionable by everyone or both of us need permission to mention all roles";
+const ROLE_DM_MESSAGE: &str = "Role reminders are posted in the channel, they can't go by DM";
+const ADMIN_ONLY_MESSAGE: &str = "Only server administrators can use this command";
+const SERVER_ONLY_MESSAGE: &str = "Server settings can only be changed in a server";
+
+/// How the outcome of setting a reminder is shown.
+#[derive(Debug, PartialEq)]
+struct Confirmation {
+    reaction: Option<char>,
+    reply: Option<String>,
+}
+
+/// With `CONFIRM_WITH_REACTION` a saved reminder only gets a ✅, a failed
+/// one gets a ❌ and the reason, so busy channels aren't cluttered. `saved` is
+/// `Ok(false)` when the message already set a reminder.
+fn confirmation<E>(
+    saved: &Result<bool, E>,
+    with_reaction: bool,
+    set_message: String,
+) -> Confirmation {
+    match saved {
+        Ok(false) => Confirmation {
+            reaction: None,
+            reply: Some(ALREADY_SET_MESSAGE.to_string()),
+        },
+        Ok(true) if with_reaction => Confirmation {
+            reaction: Some('✅'),
+            reply: None,
+        },
+        Ok(true) => Confirmation {
+            reaction: None,
+            reply: Some(set_message),
+        },
+        Err(_) => Confirmation {
+            reaction: with_reaction.then_some('❌'),
+            reply: Some(INSERT_FAILED_MESSAGE.to_string()),
+        },
+    }
+}
+
+/// Why a reminder wasn't set, for replies that don't go through
+/// [`confirmation`]. `None` once it's saved.
+fn not_saved_message<E>(saved: &Result<bool, E>) -> Option<&'static str> {
+    match saved {
+        Ok(true) => None,
+        Ok(false) => Some(ALREADY_SET_MESSAGE),
+        Err(_) => Some(INSERT_FAILED_MESSAGE),
+    }
+}
+
+fn is_expired(last: Instant, now: Instant, cooldown: Duration) -> bool {
+    now.saturating_duration_since(last) >= cooldown
+}
+
+fn on_cooldown(last: Option<Instant>, now: Instant, cooldown: Duration) -> bool {
+    last.is_some_and(|last| !is_expired(last, now, cooldown))
+}
+
+/// A user with `limit` pending reminders can't set another one.
+fn reached_reminder_limit(pending: i64, limit: i64) -> bool {
+    pending >= limit
+}
+
+fn beyond_horizon(trigger_time: DateTime<Utc>, now: DateTime<Utc>, max_years: u32) -> bool {
+    now.checked_add_months(Months::new(max_years.saturating_mul(12)))
+        .is_none_or(|horizon| trigger_time > horizon)
+}
+
+fn horizon_message(max_years: u32) -> String {
+    format!(
+        "That's too far in the future, reminders can be at most {} years ahead",
+        max_years
+    )
+}
+
+fn reminder_limit_message(prefix: &str) -> String {
+    format!(
+        "You've reached your reminder limit, cancel some with `{}cancel` before adding more",
+        prefix
+    )
+}
+
+fn date_error_message(
+    error: &DateParseError,
+    date_str: &str,
+    prefix: &str,
+    locale: &str,
+) -> String {
+    match error {
+        DateParseError::UnknownFormat => invalid_date_message(date_str, prefix, locale),
+        DateParseError::OutOfRange => MessageBuilder::new()
+            .push("There's no such date or time as ")
+            .push_mono_safe(date_str)
+            .build(),
+        DateParseError::InPast => PAST_DATE_MESSAGE.to_string(),
+        DateParseError::Overflow => OVERFLOW_MESSAGE.to_string(),
+    }
+}
+
+fn invalid_date_message(date_str: &str, prefix: &str, locale: &str) -> String {
+    MessageBuilder::new()
+        .push(t(locale, "invalid_date"))
+        .push(" ")
+        .push_mono_safe(date_str)
+        .push(
+            ". Use `YYYY-MM-DD HH:MM`, a time of day like `9am` or `17:30`, or a duration like `30s`, `30min`, `2h`, `1d12h`, `2w`, `3mo` or `1y`, ",
+        )
+        .push(format!("for example `{}remindme 1d water the plants`", prefix))
+        .build()
+}
+
+const EXPORT_FILE_NAME: &str = "reminders.json";
+const NOTHING_TO_EXPORT_MESSAGE: &str = "You have no pending reminders to export";
+
+/// `time` is written the way `!remindme import` reads it back, in `timezone`.
+#[derive(Debug, Serialize)]
+struct ExportedReminder {
+    time: String,
+    timezone: String,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    every: Option<String>,
+}
+
+fn export_reminders(reminders: &[Reminder], timezone: Option<Tz>) -> Vec<ExportedReminder> {
+    reminders
+        .iter()
+        .map(|reminder| ExportedReminder {
+            time: match timezone {
+                Some(tz) => reminder
+                    .trigger_time
+                    .with_timezone(&tz)
+                    .format("%Y-%m-%d %H:%M"),
+                None => reminder.trigger_time.format("%Y-%m-%d %H:%M"),
+            }
+            .to_string(),
+            timezone: timezone.map_or("UTC".to_string(), |tz| tz.name().to_string()),
+            message: reminder.message_content.clone(),
+            every: reminder.recurrence_interval.clone(),
+        })
+        .collect()
+}
+
+const MAX_IMPORTED_REMINDERS: usize = 100;
+const MAX_IMPORT_FILE_SIZE: u32 = 256 * 1024;
+const MAX_LISTED_IMPORT_FAILURES: usize = 10;
+const IMPORT_USAGE_MESSAGE: &str = "Attach a JSON file with a list like `[{\"time\": \"1d\", \"message\": \"stretch\"}]` to import reminders";
+const IMPORT_TOO_LARGE_MESSAGE: &str = "That file is too large to import";
+const IMPORT_DOWNLOAD_MESSAGE: &str = "I couldn't download that file, please try again later";
+
+/// One reminder in an import file, `time` takes anything `!remindme` does.
+/// `timezone` and `every` come from `!remindme export`, the first overriding
+/// the user's own timezone for `time`.
+#[derive(Debug, Default, PartialEq, Deserialize)]
+struct ImportRow {
+    time: String,
+    #[serde(default)]
+    message: String,
+    #[serde(default)]
+    timezone: Option<String>,
+    #[serde(default)]
+    every: Option<String>,
+}
+
+/// `None` for anything but a JSON list of rows.
+fn parse_import(bytes: &[u8]) -> Option<Vec<ImportRow>> {
+    serenity::json::from_slice(bytes).ok()
+}
+
+/// The trigger time, message and recurrence to store for a row, or why it
+/// was rejected.
+fn validate_import_row(
+    row: &ImportRow,
+    timezone: Option<Tz>,
+    now: DateTime<Utc>,
+    config: &Config,
+    prefix: &str,
+    locale: &str,
+) -> Result<(DateTime<Utc>, String, Option<String>), String> {
+    let timezone = match &row.timezone {
+        Some(name) => Some(
+            name.parse::<Tz>()
+                .map_err(|_| unknown_timezone_message(name))?,
+        ),
+        None => timezone,
+    };
+    let trigger_time = validate_trigger_time(&row.time, timezone, now, config, prefix, locale)?;
+    if let Some(every) = &row.every {
+        if next_recurrence_after(every, trigger_time, trigger_time, timezone).is_none() {
+            return Err(match cron_expression(every) {
+                Some(expression) => invalid_cron_message(expression, prefix),
+                None => date_error_message(&DateParseError::UnknownFormat, every, prefix, locale),
+            });
+        }
+    }
+    let (message_content, _) = limit_length(&row.message, config.max_message_length);
+    Ok((trigger_time, message_content, row.every.clone()))
+}
+
+fn unknown_timezone_message(name: &str) -> String {
+    MessageBuilder::new()
+        .push("There's no timezone called ")
+        .push_mono_safe(name)
+        .build()
+}
+
+/// A time ahead and within the horizon for `date_str`, or why it isn't one.
+fn validate_trigger_time(
+    date_str: &str,
+    timezone: Option<Tz>,
+    now: DateTime<Utc>,
+    config: &Config,
+    prefix: &str,
+    locale: &str,
+) -> Result<DateTime<Utc>, String> {
+    let trigger_time = parse_date_str(
+        date_str.trim(),
+        timezone,
+        now,
+        config.default_time,
+        &config.holidays,
+    )
+    .map_err(|e| date_error_message(&e, date_str, prefix, locale))?;
+    if beyond_horizon(trigger_time, now, config.max_horizon_years) {
+        return Err(horizon_message(config.max_horizon_years));
+    }
+    Ok(trigger_time)
+}
+
+/// Imported reminders all come from one message but need their own ids, the
+/// row number after a `/` keeps them apart.
+fn imported_message_id(message_id: MessageId, row_number: usize) -> String {
+    format!("{}/{}", message_id, row_number)
+}
+
+/// The message a reminder was set with, whatever suffix it was stored with.
+fn reference_message_id(message_id: &str) -> Option<MessageId> {
+    message_id.split('/').next()?.parse().ok()
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct ImportSummary {
+    imported: usize,
+    /// Row numbers, counted from 1, with why they weren't imported.
+    failed: Vec<(usize, String)>,
+    /// Rows past `MAX_IMPORTED_REMINDERS` that weren't read at all.
+    skipped: usize,
+}
+
+fn import_summary_message(summary: &ImportSummary) -> String {
+    let mut message = MessageBuilder::new();
+    message.push(format!(
+        "Imported {} of {} reminders",
+        summary.imported,
+        summary.imported + summary.failed.len() + summary.skipped
+    ));
+    if summary.skipped > 0 {
+        message.push(format!(
+            ", only the first {} rows are read",
+            MAX_IMPORTED_REMINDERS
+        ));
+    }
+    for (row_number, reason) in summary.failed.iter().take(MAX_LISTED_IMPORT_FAILURES) {
+        message
+            .push(format!("\n- row {}: ", row_number))
+            .push(reason);
+    }
+    if summary.failed.len() > MAX_LISTED_IMPORT_FAILURES {
+        message.push(format!(
+            "\n...and {} more",
+            summary.failed.len() - MAX_LISTED_IMPORT_FAILURES
+        ));
+    }
+    message.build()
+}
+
+const TEST_REMINDER_CONTENT: &str = "This is a test reminder";
+const TEST_SCHEDULED_MESSAGE: &str =
+    "Test reminder scheduled, it should arrive here in about a minute";
+
+/// Goes through the same insert, check and send path as any other reminder.
+fn test_reminder(
+    user_id: String,
+    channel_id: String,
+    message_id: String,
+    now: DateTime<Utc>,
+) -> Reminder {
+    Reminder {
+        id: None,
+        target_user_id: user_id.clone(),
+        user_id,
+        channel_id,
+        message_id,
+        message_content: TEST_REMINDER_CONTENT.to_string(),
+        trigger_time: now + chrono::Duration::minutes(1),
+        recurrence_interval: None,
+        status: STATUS_PENDING.to_string(),
+        attempts: 0,
+        deliver_dm: false,
+        remaining_occurrences: None,
+        created_at: None,
+        target_role_id: None,
+        priority: PRIORITY_NORMAL,
+        deleted_at: None,
+        tag: None,
+        retry_at: None,
+        source: Some(SOURCE_TEXT.to_string()),
+    }
+}
+
+/// The `time` option and the optional `message`, `None` without a time.
+fn parse_remindme_options(options: &[ResolvedOption]) -> Option<(String, Option<String>)> {
+    let mut date_str = None;
+    let mut text = None;
+    for option in options {
+        if let ResolvedValue::String(value) = option.value {
+            match option.name {
+                "time" => date_str = Some(value.to_string()),
+                "message" => text = Some(value.to_string()),
+                _ => {}
+            }
+        }
+    }
+    date_str.map(|date_str| (date_str, text))
+}
+
+#[derive(Default)]
+struct DiscordCache {
+    users: HashMap<UserId, User>,
+    channels: HashMap<ChannelId, Channel>,
+    saved_calls: usize,
+}
+
+impl DiscordCache {
+    async fn user(&mut self, http: &Http, user_id: UserId) -> Result<User, serenity::Error> {
+        get_or_fetch(&mut self.users, &mut self.saved_calls, user_id, || {
+            with_retries(|| http.get_user(user_id))
+        })
+        .await
+    }
+
+    async fn channel(
+        &mut self,
+        http: &Http,
+        channel_id: ChannelId,
+    ) -> Result<Channel, serenity::Error> {
+        get_or_fetch(
+            &mut self.channels,
+            &mut self.saved_calls,
+            channel_id,
+            || with_retries(|| http.get_channel(channel_id)),
+        )
+        .await
+    }
+}
+
+/// Failed fetches aren't kept, so the next reminder for the same id tries again.
+async fn get_or_fetch<K, V, E, F, Fut>(
+    cache: &mut HashMap<K, V>,
+    saved_calls: &mut usize,
+    id: K,
+    fetch: F,
+) -> Result<V, E>
+where
+    K: Eq + std::hash::Hash,
+    V: Clone,
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<V, E>>,
+{
+    if let Some(value) = cache.get(&id) {
+        *saved_calls += 1;
+        return Ok(value.clone());
+    }
+    let value = fetch().await?;
+    cache.insert(id, value.clone());
+    Ok(value)
+}
+
+#[derive(Debug)]
+enum SendReminderError {
+    InvalidUserId,
+    InvalidRoleId,
+    InvalidChannelId,
+    ChannelNotFound,
+    UnsupportedChannel,
+    DiscordApi(serenity::Error),
+}
+
+impl fmt::Display for SendReminderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SendReminderError::InvalidUserId => write!(f, "invalid user id"),
+            SendReminderError::InvalidRoleId => write!(f, "invalid role id"),
+            SendReminderError::InvalidChannelId => write!(f, "invalid channel id"),
+            SendReminderError::ChannelNotFound => write!(f, "channel not found"),
+            SendReminderError::UnsupportedChannel => write!(f, "unsupported channel type"),
+            SendReminderError::DiscordApi(e) => write!(f, "discord api error: {}", e),
+        }
+    }
+}
+
+impl From<serenity::Error> for SendReminderError {
+    fn from(e: serenity::Error) -> Self {
+        SendReminderError::DiscordApi(e)
+    }
+}
+
+fn status_code(e: &serenity::Error) -> Option<u16> {
+    match e {
+        serenity::Error::Http(e) => e.status_code().map(|code| code.as_u16()),
+        _ => None,
+    }
+}
+
+fn is_not_found(e: &serenity::Error) -> bool {
+    status_code(e) == Some(404)
+}
+
+/// Rate limits and server errors can pass on their own, anything else like
+/// a deleted channel won't.
+fn is_retryable_status(status: u16) -> bool {
+    status == 429 || (500..600).contains(&status)
+}
+
+const MAX_SEND_RETRIES: u32 = 3;
+
+/// 1s before the first retry, doubling for each one after.
+fn retry_delay(retry: u32) -> Duration {
+    Duration::from_secs(1 << retry)
+}
+
+async fn with_retries<T, F, Fut>(mut call: F) -> Result<T, serenity::Error>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, serenity::Error>>,
+{
+    let mut retry = 0;
+    loop {
+        match call().await {
+            Err(e)
+                if retry < MAX_SEND_RETRIES && status_code(&e).is_some_and(is_retryable_status) =>
+            {
+                let delay = retry_delay(retry);
+                warn!("Discord API error, retrying in {:?}: {:?}", delay, e);
+                tokio::time::sleep(delay).await;
+                retry += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// The first id stored as text that doesn't parse, so a row broken by hand
+/// or by a migration is caught before it's grouped with others.
+fn malformed_id(reminder: &Reminder) -> Option<SendReminderError> {
+    let user_ids = [&reminder.user_id, &reminder.target_user_id];
+    if user_ids.iter().any(|id| id.parse::<UserId>().is_err()) {
+        return Some(SendReminderError::InvalidUserId);
+    }
+    if reminder.channel_id.parse::<ChannelId>().is_err() {
+        return Some(SendReminderError::InvalidChannelId);
+    }
+    let role_id = reminder.target_role_id.as_deref();
+    if role_id.is_some_and(|id| id.parse::<RoleId>().is_err()) {
+        return Some(SendReminderError::InvalidRoleId);
+    }
+    None
+}
+
+/// Sends one message for reminders that share a channel, target and delivery.
+async fn send_reminders(
+    http: &Http,
+    cache: &mut DiscordCache,
+    reminder_template: Option<&str>,
+    embeds: bool,
+    reminders: &[Reminder],
+) -> Result<(), SendReminderError> {
+    let Some(first) = reminders.first() else {
+        return Ok(());
+    };
+    let target_user_id = first
+        .target_user_id
+        .parse::<UserId>()
+        .map_err(|_| SendReminderError::InvalidUserId)?;
+    let channel_id = first
+        .channel_id
+        .parse::<ChannelId>()
+        .map_err(|_| SendReminderError::InvalidChannelId)?;
+    let creator_ids = reminders
+        .iter()
+        .map(|reminder| reminder.user_id.parse::<UserId>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| SendReminderError::InvalidUserId)?;
+    let role_id = first
+        .target_role_id
+        .as_deref()
+        .map(str::parse::<RoleId>)
+        .transpose()
+        .map_err(|_| SendReminderError::InvalidRoleId)?;
+
+    let user = cache.user(http, target_user_id).await?;
+    let target = role_id.map_or(Mention::User(user.id), Mention::Role);
+
+    let now = Utc::now();
+    let mut entries = Vec::new();
+    for (reminder, creator_id) in reminders.iter().zip(creator_ids) {
+        // The reference message is only a convenience, the reminder still goes
+        // out if it was deleted.
+        let reference_link = match reference_message_id(&reminder.message_id) {
+            Some(message_id) => match channel_id.message(http, message_id).await {
+                Ok(message) => Some(message.link()),
+                Err(e) => {
+                    debug!("Reference message unavailable: {:?}", e);
+                    None
+                }
+            },
+            None => None,
+        };
+        entries.push(ReminderEntry {
+            creator_id,
+            content: &reminder.message_content,
+            reference_link,
+            set_ago: reminder
+                .created_at
+                .map(|created_at| time_ago(now - created_at)),
+        });
+    }
+
+    // Reminders fired together keep the built-in list, one embed each would
+    // soon run into Discord's limit of ten.
+    let embed = match entries.as_slice() {
+        [entry] if embeds => {
+            let avatar_url = match cache.user(http, entry.creator_id).await {
+                Ok(creator) => Some(creator.face()),
+                Err(e) => {
+                    debug!("Creator unavailable for the embed thumbnail: {:?}", e);
+                    None
+                }
+            };
+            Some(reminder_embed(
+                entry.content,
+                entry.reference_link.as_deref(),
+                first.created_at,
+                avatar_url,
+            ))
+        }
+        _ => None,
+    };
+
+    let reminder_response = match (entries.as_slice(), reminder_template) {
+        // The embed carries the reminder, the text is only there to ping.
+        _ if embed.is_some() => target.to_string(),
+        ([entry], Some(template)) => render_reminder_template(
+            template,
+            target,
+            entry.content,
+            entry.reference_link.as_deref(),
+        ),
+        ([entry], None) => build_reminder_message(
+            target,
+            entry.creator_id,
+            entry.content,
+            entry.reference_link.as_deref(),
+            entry.set_ago.as_deref(),
+        ),
+        (entries, _) => build_grouped_reminder_message(target, entries),
+    };
+
+    let parts = split_message(&reminder_response, DISCORD_MESSAGE_LIMIT);
+
+    // Users with DMs disabled still get the reminder in the original channel.
+    if first.deliver_dm {
+        let dm = |part: &str| with_embed(CreateMessage::new().content(part), embed.as_ref());
+        match send_parts(&parts, |part| {
+            with_retries(|| user.direct_message(http, dm(part)))
+        })
+        .await
+        {
+            Ok(()) => return Ok(()),
+            Err(e) => warn!(
+                "Error sending reminder by DM, falling back to channel: {:?}",
+                e
+            ),
+        }
+    }
+
+    let channel = cache.channel(http, channel_id).await.map_err(|e| {
+        if is_not_found(&e) {
+            SendReminderError::ChannelNotFound
+        } else {
+            SendReminderError::DiscordApi(e)
+        }
+    })?;
+    match reminder_destination(&channel) {
+        Some(destination) => debug!(?destination, "Sending reminder"),
+        None => return Err(SendReminderError::UnsupportedChannel),
+    }
+    send_parts(&parts, |part| {
+        with_retries(|| {
+            let message = channel_reminder_message(part, role_id);
+            channel_id.send_message(http, with_embed(message, embed.as_ref()))
+        })
+    })
+    .await?;
+    Ok(())
+}
+
+/// Sends `parts` in order. Only a failed first part fails the send, after
+/// that the reminder is out and retrying it would repeat what already got
+/// through, so the rest is logged instead.
+async fn send_parts<'a, T, E, F, Fut>(parts: &'a [String], mut send: F) -> Result<(), E>
+where
+    E: fmt::Debug,
+    F: FnMut(&'a str) -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let Some((first, rest)) = parts.split_first() else {
+        return Ok(());
+    };
+    send(first).await?;
+    for (index, part) in rest.iter().enumerate() {
+        if let Err(e) = send(part).await {
+            error!(
+                part = index + 2,
+                parts = parts.len(),
+                "Error sending the rest of a reminder: {:?}",
+                e
+            );
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// What kind of channel a reminder goes to.
+#[derive(Debug, PartialEq)]
+enum ReminderDestination {
+    /// A server channel with its own chat: text, announcement, voice or stage.
+    GuildText,
+    Thread,
+    Private,
+}
+
+/// `None` for channels nothing can be posted in directly, like categories,
+/// forums and directories, or kinds this version of serenity doesn't know.
+fn reminder_destination(channel: &Channel) -> Option<ReminderDestination> {
+    match channel {
+        Channel::Guild(channel) => match channel.kind {
+            ChannelType::Text | ChannelType::News | ChannelType::Voice | ChannelType::Stage => {
+                Some(ReminderDestination::GuildText)
+            }
+            ChannelType::PublicThread | ChannelType::PrivateThread | ChannelType::NewsThread => {
+                Some(ReminderDestination::Thread)
+            }
+            _ => None,
+        },
+        Channel::Private(_) => Some(ReminderDestination::Private),
+        _ => None,
+    }
+}
+
+fn with_embed(message: CreateMessage, embed: Option<&CreateEmbed>) -> CreateMessage {
+    match embed {
+        Some(embed) => message.embed(embed.clone()),
+        None => message,
+    }
+}
+
+/// A single reminder as an embed, for `REMINDER_EMBEDS`.
+fn reminder_embed(
+    content: &str,
+    reference_link: Option<&str>,
+    set_at: Option<DateTime<Utc>>,
+    avatar_url: Option<String>,
+) -> CreateEmbed {
+    let mut embed = CreateEmbed::new()
+        .title("Reminder")
+        .description(if content.is_empty() {
+            "You didn't leave a message with this one"
+        } else {
+            content
+        });
+    if let Some(link) = reference_link {
+        embed = embed.url(link);
+    }
+    if let Some(set_at) = set_at {
+        embed = embed.field("Set", format_trigger_time(set_at, None), true);
+    }
+    if let Some(avatar_url) = avatar_url {
+        embed = embed.thumbnail(avatar_url);
+    }
+    embed
+}
+
+/// Role pings are let through only for the role the reminder was set for,
+/// the bot may be allowed to ping others the text happens to mention.
+fn channel_reminder_message(content: &str, role_id: Option<RoleId>) -> CreateMessage {
+    let message = CreateMessage::new().content(content);
+    match role_id {
+        Some(role_id) => message.allowed_mentions(
+            CreateAllowedMentions::new()
+                .all_users(true)
+                .roles([role_id]),
+        ),
+        None => message,
+    }
+}
+
+/// Everything `help` lists, with `prefix` in front of each command. Longer
+/// than one Discord message, so send it through [`split_message`].
+fn help_message(prefix: &str) -> String {
+    MessageBuilder::new()
+        .push("I can remind you about something in the future. ")
+        .push(format!("To set a reminder, use the `{p}remindme` command followed by a date and time. ", p = prefix))
+        .push(format!("For example, `{p}remindme 2021-01-01-12-00`, `{p}remindme 9am`, `{p}remindme 17:30`, `{p}remindme 1d`, `{p}remindme 2w` or `{p}remindme 3mo` ", p = prefix))
+        .push("Durations use `s`, `min`, `h`, `d`, `w`, `mo` and `y`, combined like `1h30min`, and `bd` counts business days, skipping weekends, like `3bd`. ")
+        .push(format!("Plain words work too: `{p}remindme tomorrow 9am`, `{p}remindme next friday` or `{p}remindme in 2 hours`. ", p = prefix))
+        .push(format!("You can also add a message to the reminder, like this: `{p}remindme 2021-01-01-12-00 don't forget to call mom` ", p = prefix))
+        .push(format!("Mention someone first to remind them instead, like `{p}remindme @friend 1h call me`. ", p = prefix))
+        .push(format!("Mention a role first to ping it instead, like `{p}remindme @team 1d standup`. ", p = prefix))
+        .push(format!("Mention a channel first to have it fire there, like `{p}remindme #announcements 1d release day`. ", p = prefix))
+        .push(format!("Start with `!high`, like `{p}remindme !high 1h deploy`, to have it go out before others due at the same time. ", p = prefix))
+        .push(format!("Add `dm` to get the reminder in your DMs, like `{p}remindme dm 1h stretch`. ", p = prefix))
+        .push(format!("Add `every` for a repeating reminder, like `{p}remindme every 1d take vitamins` or `{p}remindme every monday 9am standup`, or `repeat <n> times every` to stop after a few, like `{p}remindme repeat 5 times every 2h drink water`. ", p = prefix))
+        .push(format!("For anything more involved use a cron expression in your timezone, like `{p}remindme cron \"0 9 * * 1-5\" standup`. ", p = prefix))
+        .push(format!("End a reminder with a tag like `#work` to list only those with `{p}reminders work`. ", p = prefix))
+        .push(format!("Use `{p}reminders` to list your pending reminders and `{p}cancel <number>` or `{p}cancel text <part of the message>` to cancel one, `{p}undo` to remove the one you set last, `{p}remindme cancel all` to cancel all of them or `{p}edit <number> <new message>` to change its message. ", p = prefix))
+        .push(format!("Use `{p}whenis <date>` to check how a date is read without setting anything. ", p = prefix))
+        .push(format!("Use `{p}remindme after <number> +30m` to set one relative to another of your reminders. ", p = prefix))
+        .push(format!("Use `{p}remindme test` to check reminders reach this channel. ", p = prefix))
+        .push(format!("Use `{p}remindme export` to get your pending reminders as a file. ", p = prefix))
+        .push(format!("Attach a JSON file like `[{{\"time\": \"1d\", \"message\": \"stretch\"}}]` to `{p}remindme import` to set many reminders at once. ", p = prefix))
+        .push(format!("Use `{p}reschedule <number> <new date>` to move one of your reminders. ", p = prefix))
+        .push(format!("Use `{p}snooze <duration>` to get your last fired reminder again later. ", p = prefix))
+        .push(format!("Server administrators can use `{p}upcoming <hours>` to see how many reminders are due soon and `{p}settings` to change this server's prefix or reminder limit. ", p = prefix))
+        .push(format!("Use `{p}digest on` for a daily DM listing your next day of reminders. ", p = prefix))
+        .push(format!("Use `{p}locale <language>` to get replies in {}. ", LOCALES.join(", "), p = prefix))
+        .push(format!("Dates are read in the server's timezone unless you set your own with `{p}timezone <IANA name>`, e.g. `{p}timezone Europe/Warsaw`.", p = prefix))
+        .build()
+}
+
+const DISCORD_MESSAGE_LIMIT: usize = 2000;
+
+/// Pieces of at most `limit` characters, always at least one. Breaks at the
+/// last newline or space that fits, so words and links stay whole where
+/// they can.
+fn split_message(text: &str, limit: usize) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut rest = text;
+    while rest.chars().count() > limit {
+        let end = rest
+            .char_indices()
+            .nth(limit)
+            .map_or(rest.len(), |(index, _)| index);
+        let head = &rest[..end];
+        match head.rfind(['\n', ' ']).filter(|&index| index > 0) {
+            Some(index) => {
+                parts.push(head[..index].to_string());
+                // The newline or space itself is dropped.
+                rest = &rest[index + 1..];
+            }
+            None => {
+                parts.push(head.to_string());
+                rest = &rest[end..];
+            }
+        }
+    }
+    parts.push(rest.to_string());
+    parts
+}
+
+struct ReminderEntry<'a> {
+    creator_id: UserId,
+    content: &'a str,
+    reference_link: Option<String>,
+    /// Like `3 days ago`, `None` when it's unknown when it was set.
+    set_ago: Option<String>,
+}
+
+/// How long ago something happened in its largest whole unit, like `3 days ago`.
+fn time_ago(elapsed: chrono::Duration) -> String {
+    const UNITS: [(&str, i64); 6] = [
+        ("year", 365 * 24 * 60 * 60),
+        ("month", 30 * 24 * 60 * 60),
+        ("week", 7 * 24 * 60 * 60),
+        ("day", 24 * 60 * 60),
+        ("hour", 60 * 60),
+        ("minute", 60),
+    ];
+    let seconds = elapsed.num_seconds();
+    match UNITS
+        .iter()
+        .find(|(_, unit_seconds)| seconds >= *unit_seconds)
+    {
+        Some((unit, unit_seconds)) => match seconds / unit_seconds {
+            1 => format!("1 {} ago", unit),
+            count => format!("{} {}s ago", count, unit),
+        },
+        None => "a moment ago".to_string(),
+    }
+}
+
+fn build_reminder_message(
+    target: Mention,
+    creator_id: UserId,
+    content: &str,
+    reference_link: Option<&str>,
+    set_ago: Option<&str>,
+) -> String {
+    let mut message = MessageBuilder::new();
+    message.push("Hey ").mention(&target).push(", ");
+    if let Some(set_ago) = set_ago {
+        message.push(set_ago).push(" ");
+    }
+    if is_user(target, creator_id) {
+        message.push("you asked me to remind you about this: ");
+    } else {
+        message
+            .mention(&creator_id)
+            .push(" asked me to remind you about this: ");
+    }
+    message.push(content);
+    if let Some(link) = reference_link {
+        message.push(" reference message: ").push(link);
+    }
+    message.build()
+}
+
+/// Fills in `{user}`, `{message}` and `{link}` in one pass, so a reminder
+/// whose text contains a placeholder isn't expanded again. Without a
+/// reference message `{link}` is left empty.
+fn render_reminder_template(
+    template: &str,
+    target: Mention,
+    content: &str,
+    reference_link: Option<&str>,
+) -> String {
+    let placeholder = Regex::new(r"\{(user|message|link)\}").unwrap();
+    placeholder
+        .replace_all(template, |caps: &Captures| match &caps[1] {
+            "user" => target.to_string(),
+            "message" => content.to_string(),
+            _ => reference_link.unwrap_or_default().to_string(),
+        })
+        .into_owned()
+}
+
+fn is_user(target: Mention, user_id: UserId) -> bool {
+    matches!(target, Mention::User(target_id) if target_id == user_id)
+}
+
+/// One line per reminder, mentioning the target only once.
+fn build_grouped_reminder_message(target: Mention, entries: &[ReminderEntry]) -> String {
+    let mut message = MessageBuilder::new();
+    message
+        .push("Hey ")
+        .mention(&target)
+        .push_line(", you asked me to remind you about these:");
+    for entry in entries {
+        message.push("- ").push(entry.content);
+        match (!is_user(target, entry.creator_id), &entry.set_ago) {
+            (true, Some(set_ago)) => {
+                message
+                    .push(" (from ")
+                    .mention(&entry.creator_id)
+                    .push(", ")
+                    .push(set_ago)
+                    .push(")");
+            }
+            (true, None) => {
+                message.push(" (from ").mention(&entry.creator_id).push(")");
+            }
+            (false, Some(set_ago)) => {
+                message.push(" (").push(set_ago).push(")");
+            }
+            (false, None) => {}
+        }
+        if let Some(link) = &entry.reference_link {
+            message.push(" reference message: ").push(link);
+        }
+        message.push_line("");
+    }
+    message.build()
+}
+
+/// Room left in a grouped message for everything but the reminders' content,
+/// keeping it under Discord's 2000 character limit.
+const GROUPED_MESSAGE_LENGTH: usize = 1800;
+const GROUPED_LINE_OVERHEAD: usize = 120;
+
+/// Due reminders for the same person in the same place, in the order they
+/// came, starting a new group when one message would get too long.
+fn group_reminders(reminders: Vec<Reminder>) -> Vec<Vec<Reminder>> {
+    let line_length =
+        |reminder: &Reminder| reminder.message_content.chars().count() + GROUPED_LINE_OVERHEAD;
+    let mut groups: Vec<Vec<Reminder>> = Vec::new();
+    for reminder in reminders {
+        let group = groups.iter_mut().find(|group| {
+            let first = &group[0];
+            first.channel_id == reminder.channel_id
+                && first.target_user_id == reminder.target_user_id
+                && first.target_role_id == reminder.target_role_id
+                && first.deliver_dm == reminder.deliver_dm
+                && group.iter().map(line_length).sum::<usize>() + line_length(&reminder)
+                    <= GROUPED_MESSAGE_LENGTH
+        });
+        match group {
+            Some(group) => group.push(reminder),
+            None => groups.push(vec![reminder]),
+        }
+    }
+    groups
+}
+
+/// Every job gets its own clones up front, so `main` keeps ownership of its
+/// pool and http handles no matter how many jobs are added here.
+fn register_jobs(
+    scheduler: &mut AsyncScheduler,
+    pool: &PgPool,
+    http: &Arc<Http>,
+    check_interval: u32,
+    firing: FiringOptions,
+    purge_after_days: u32,
+    digest_hour: u32,
+) {
+    let job_pool = pool.clone();
+    let job_http = http.clone();
+    let backoff = Arc::new(Mutex::new(DatabaseBackoff::default()));
+    scheduler.every(check_interval.seconds()).run(move || {
+        let pool = job_pool.clone();
+        let http = job_http.clone();
+        let backoff = backoff.clone();
+        let firing = firing.clone();
+
+        async move {
+            check_reminders_job(pool, http, firing, backoff).await;
+        }
+    });
+
+    let job_pool = pool.clone();
+    scheduler.every(1.hour()).run(move || {
+        let pool = job_pool.clone();
+
+        async move {
+            purge_reminders_job(pool, purge_after_days).await;
+        }
+    });
+
+    // Often enough to land inside the digest hour in every timezone, even
+    // ones offset by half an hour.
+    let job_pool = pool.clone();
+    let job_http = http.clone();
+    scheduler.every(10.minutes()).run(move || {
+        let pool = job_pool.clone();
+        let http = job_http.clone();
+
+        async move {
+            digest_job(pool, http, digest_hour).await;
+        }
+    });
+}
+
+async fn digest_job(pool: PgPool, http: Arc<Http>, digest_hour: u32) {
+    let subscribers = match get_digest_subscribers(&pool).await {
+        Ok(subscribers) => subscribers,
+        Err(e) => {
+            error!("Error getting digest subscribers: {:?}", e);
+            return;
+        }
+    };
+    let now = Utc::now();
+    let due: Vec<(&DigestSubscriber, NaiveDate)> = subscribers
+        .iter()
+        .filter_map(|subscriber| Some((subscriber, digest_due(subscriber, digest_hour, now)?)))
+        .collect();
+    if due.is_empty() {
+        return;
+    }
+
+    let reminders =
+        match get_reminders_due_between(&pool, now, now + chrono::Duration::hours(24)).await {
+            Ok(reminders) => reminders,
+            Err(e) => {
+                error!("Error getting reminders for digests: {:?}", e);
+                return;
+            }
+        };
+    let user_ids: Vec<&str> = due
+        .iter()
+        .map(|(subscriber, _)| subscriber.user_id.as_str())
+        .collect();
+    let digests = group_by_user(&user_ids, &reminders);
+
+    for (subscriber, today) in due {
+        // Nothing coming up means nothing to send, but still counts as today's.
+        if let Some(reminders) = digests.get(subscriber.user_id.as_str()) {
+            let Ok(user_id) = subscriber.user_id.parse::<UserId>() else {
+                warn!(user_id = %subscriber.user_id, "Skipping digest for a malformed user id");
+                continue;
+            };
+            let digest = format_digest(reminders, subscriber.timezone);
+            if let Err(e) = send_digest(&http, user_id, &digest).await {
+                warn!(%user_id, "Error sending digest: {:?}", e);
+                continue;
+            }
+        }
+        if let Err(e) = mark_digest_sent(&pool, &subscriber.user_id, today).await {
+            error!(user_id = %subscriber.user_id, "Error recording sent digest: {:?}", e);
+        }
+    }
+}
+
+async fn send_digest(http: &Http, user_id: UserId, digest: &str) -> Result<(), serenity::Error> {
+    let channel = with_retries(|| user_id.create_dm_channel(http)).await?;
+    for part in split_message(digest, DISCORD_MESSAGE_LIMIT) {
+        with_retries(|| channel.say(http, &part)).await?;
+    }
+    Ok(())
+}
+
+/// The subscriber's date when their digest is due now, during `digest_hour`
+/// in their timezone and only once a day.
+fn digest_due(
+    subscriber: &DigestSubscriber,
+    digest_hour: u32,
+    now: DateTime<Utc>,
+) -> Option<NaiveDate> {
+    let local = match subscriber.timezone {
+        Some(tz) => now.with_timezone(&tz).naive_local(),
+        None => now.naive_utc(),
+    };
+    let today = local.date();
+    let sent_today = subscriber.last_sent_on.is_some_and(|last| last >= today);
+    (local.hour() == digest_hour && !sent_today).then_some(today)
+}
+
+/// The reminders set by each of `user_ids`, in the order given, leaving out
+/// everyone else and anyone with none.
+fn group_by_user<'a>(
+    user_ids: &[&str],
+    reminders: &'a [Reminder],
+) -> HashMap<&'a str, Vec<&'a Reminder>> {
+    let mut groups: HashMap<&str, Vec<&Reminder>> = HashMap::new();
+    for reminder in reminders {
+        if user_ids.contains(&reminder.user_id.as_str()) {
+            groups.entry(&reminder.user_id).or_default().push(reminder);
+        }
+    }
+    groups
+}
+
+fn format_digest(reminders: &[&Reminder], timezone: Option<Tz>) -> String {
+    let mut builder = MessageBuilder::new();
+    builder.push_line("Your reminders for the next 24 hours:");
+    for reminder in reminders {
+        builder
+            .push(format!(
+                "- {} - ",
+                format_trigger_time(reminder.trigger_time, timezone)
+            ))
+            .push_line_safe(truncate(&reminder.message_content, LISTED_CONTENT_LENGTH));
+    }
+    builder.build()
+}
+
+async fn purge_reminders_job(pool: PgPool, purge_after_days: u32) {
+    let before = Utc::now() - chrono::Duration::days(purge_after_days.into());
+    match purge_deleted_reminders(&pool, before).await {
+        Ok(0) => {}
+        Ok(count) => info!("Purged {} deleted reminders", count),
+        Err(e) => error!("Error purging deleted reminders: {:?}", e),
+    }
+}
+
+/// The settings that decide how due reminders go out.
+#[derive(Clone)]
+struct FiringOptions {
+    max_send_attempts: i32,
+    retry_interval: chrono::Duration,
+    max_age: Option<chrono::Duration>,
+    cleanup: bool,
+    reminder_template: Option<Arc<str>>,
+    embeds: bool,
+    send_batch_size: usize,
+    /// Startup time when reminders were already overdue then, those are sent
+    /// `catch_up_batch_size` at a time.
+    catch_up_before: Option<DateTime<Utc>>,
+    catch_up_batch_size: usize,
+}
+
+const SEND_BATCH_PAUSE: Duration = Duration::from_secs(1);
+
+/// Whether to wait `SEND_BATCH_PAUSE` before sending the `index`th message,
+/// so a minute with many due reminders doesn't go out as one burst.
+fn pause_before(index: usize, batch_size: usize) -> bool {
+    batch_size > 0 && index > 0 && index.is_multiple_of(batch_size)
+}
+
+/// How many messages go out between pauses, fewer while reminders that came
+/// due before startup are still being caught up on.
+fn batch_size_for(firing: &FiringOptions, reminders: &[Reminder]) -> usize {
+    let catching_up = firing.catch_up_before.is_some_and(|started_at| {
+        reminders
+            .iter()
+            .any(|reminder| reminder.trigger_time < started_at)
+    });
+    match (catching_up, firing.send_batch_size) {
+        (false, batch_size) => batch_size,
+        (true, 0) => firing.catch_up_batch_size,
+        (true, batch_size) => batch_size.min(firing.catch_up_batch_size),
+    }
+}
+
+/// Skips checks for a while after the database went away, the pool opens new
+/// connections on its own once it's back.
+#[derive(Default)]
+struct DatabaseBackoff {
+    failures: u32,
+    retry_at: Option<Instant>,
+}
+
+const MAX_DATABASE_BACKOFF: Duration = Duration::from_secs(300);
+
+/// 2s after the first failure, doubling up to five minutes.
+fn database_backoff(failures: u32) -> Duration {
+    Duration::from_secs(1 << failures.min(16)).min(MAX_DATABASE_BACKOFF)
+}
+
+/// Whether the error came from reaching the database rather than the query.
+fn is_connection_error(e: &sqlx::Error) -> bool {
+    match e {
+        sqlx::Error::Io(_)
+        | sqlx::Error::Tls(_)
+        | sqlx::Error::PoolTimedOut
+        | sqlx::Error::PoolClosed
+        | sqlx::Error::WorkerCrashed => true,
+        // Class 08 is connection exceptions, 57P0x the server shutting down.
+        sqlx::Error::Database(e) => e
+            .code()
+            .is_some_and(|code| code.starts_with("08") || code.starts_with("57P0")),
+        _ => false,
+    }
+}
+
+async fn check_reminders_job(
+    pool: PgPool,
+    http: Arc<Http>,
+    firing: FiringOptions,
+    backoff: Arc<Mutex<DatabaseBackoff>>,
+) {
+    {
+        let backoff = backoff.lock().await;
+        if backoff
+            .retry_at
+            .is_some_and(|retry_at| Instant::now() < retry_at)
+        {
+            debug!("Database unavailable, skipping reminder check");
+            return;
+        }
+    }
+
+    debug!("Checking reminders");
+    let reminders = match get_due_reminders(&pool).await {
+        Ok(reminders) => {
+            let mut backoff = backoff.lock().await;
+            if backoff.failures > 0 {
+                info!("Database connection restored");
+            }
+            *backoff = DatabaseBackoff::default();
+            reminders
+        }
+        Err(e) if is_connection_error(&e) => {
+            let mut backoff = backoff.lock().await;
+            backoff.failures += 1;
+            let delay = database_backoff(backoff.failures);
+            backoff.retry_at = Some(Instant::now() + delay);
+            warn!(
+                failures = backoff.failures,
+                "Lost connection to the database, retrying in {:?}: {:?}", delay, e
+            );
+            return;
+        }
+        Err(e) => {
+            error!("Error getting reminders: {:?}", e);
+            return;
+        }
+    };
+
+    let mut sendable = Vec::new();
+    for reminder in reminders {
+        match malformed_id(&reminder) {
+            None => sendable.push(reminder),
+            Some(e) => skip_malformed_reminder(&pool, &reminder, &e).await,
+        }
+    }
+
+    let batch_size = batch_size_for(&firing, &sendable);
+    let mut cache = DiscordCache::default();
+    for (index, group) in group_reminders(sendable).into_iter().enumerate() {
+        if pause_before(index, batch_size) {
+            tokio::time::sleep(SEND_BATCH_PAUSE).await;
+        }
+        let ids: Vec<i32> = group.iter().filter_map(|reminder| reminder.id).collect();
+        let fire = fire_reminders(&pool, &http, &mut cache, &firing, group);
+        isolate(&ids, fire).await;
+    }
+
+    if cache.saved_calls > 0 {
+        debug!("Saved {} Discord API calls by caching", cache.saved_calls);
+    }
+}
+
+/// A malformed id fails the same way on every attempt, so the reminder is
+/// marked failed straight away instead of being retried.
+async fn skip_malformed_reminder(pool: &PgPool, reminder: &Reminder, e: &SendReminderError) {
+    let Some(id) = reminder.id else {
+        return;
+    };
+    error!(
+        id,
+        user_id = %reminder.user_id,
+        channel_id = %reminder.channel_id,
+        error = %e,
+        "Skipping reminder with a malformed id"
+    );
+    if let Err(e) = mark_reminder_failed(pool, id).await {
+        error!(id, "Error marking reminder failed: {:?}", e);
+    }
+}
+
+/// Errors are already handled per reminder, this keeps a panic while firing
+/// some from abandoning the rest of the batch.
+async fn isolate<Fut: Future<Output = ()>>(ids: &[i32], fire: Fut) {
+    if AssertUnwindSafe(fire).catch_unwind().await.is_err() {
+        error!(
+            ?ids,
+            "Firing reminders panicked, moving on to the next ones"
+        );
+    }
+}
+
+async fn fire_reminders(
+    pool: &PgPool,
+    http: &Http,
+    cache: &mut DiscordCache,
+    firing: &FiringOptions,
+    reminders: Vec<Reminder>,
+) {
+    // Claiming the rows before sending means a crash between sending and
+    // deleting can't deliver the same reminder twice after a restart.
+    let mut claimed = Vec::new();
+    for reminder in reminders {
+        let Some(id) = reminder.id else {
+            continue;
+        };
+        match claim_reminder(pool, id).await {
+            Ok(true) => claimed.push(reminder),
+            Ok(false) => {}
+            Err(e) => error!(id, "Error claiming reminder: {:?}", e),
+        }
+    }
+    if claimed.is_empty() {
+        return;
+    }
+
+    let sent = send_reminders(
+        http,
+        cache,
+        firing.reminder_template.as_deref(),
+        firing.embeds,
+        &claimed,
+    )
+    .await;
+    for reminder in &claimed {
+        if let Some(id) = reminder.id {
+            finish_reminder(pool, firing, id, reminder, &sent).await;
+        }
+    }
+}
+
+#[instrument(
+    skip(pool, firing, reminder, sent),
+    fields(user_id = %reminder.user_id, trigger_time = %reminder.trigger_time)
+)]
+async fn finish_reminder(
+    pool: &PgPool,
+    firing: &FiringOptions,
+    id: i32,
+    reminder: &Reminder,
+    sent: &Result<(), SendReminderError>,
+) {
+    match send_outcome(sent, reminder.trigger_time, Utc::now(), firing, &COUNTERS) {
+        SendOutcome::Delivered => info!("Reminder sent"),
+        SendOutcome::Skipped(e) => {
+            error!(error = %e, channel_id = %reminder.channel_id, "Skipping reminder");
+            if let Err(e) = mark_reminder_failed(pool, id).await {
+                error!("Error marking reminder failed: {:?}", e);
+            }
+            return;
+        }
+        SendOutcome::Abandoned(e) => {
+            error!(error = %e, "Abandoning reminder that couldn't be delivered in time");
+            // Failed first, so the removed row doesn't read as delivered.
+            if let Err(e) = mark_reminder_failed(pool, id).await {
+                error!("Error marking abandoned reminder failed: {:?}", e);
+            }
+            if let Err(e) = delete_reminder(pool, id).await {
+                error!("Error removing abandoned reminder: {:?}", e);
+            }
+            return;
+        }
+        SendOutcome::Retry(e, retry_at) => {
+            warn!(error = %e, attempt = reminder.attempts + 1, "Error sending reminder");
+            match record_failed_attempt(pool, id, firing.max_send_attempts, retry_at).await {
+                Ok(true) => error!(
+                    error = %e,
+                    "Giving up on reminder after {} attempts", firing.max_send_attempts
+                ),
+                Ok(false) => {}
+                Err(e) => error!("Error recording failed attempt: {:?}", e),
+            }
+            return;
+        }
+    }
+
+    if let Err(e) = record_sent_reminder(pool, reminder).await {
+        error!("Error recording sent reminder: {:?}", e);
+    }
+
+    match after_firing(reminder) {
+        AfterFiring::Reschedule {
+            interval,
+            remaining_occurrences,
+        } => {
+            // Weekday rules were written in the creator's timezone.
+            let timezone = match get_user_timezone(pool, &reminder.user_id).await {
+                Ok(timezone) => timezone,
+                Err(e) => {
+                    warn!("Error getting timezone, using server timezone: {:?}", e);
+                    None
+                }
+            };
+            schedule_next_occurrence(
+                pool,
+                id,
+                reminder.trigger_time,
+                interval,
+                timezone,
+                remaining_occurrences,
+            )
+            .await
+        }
+        // Kept as sent instead, for looking into what went out.
+        AfterFiring::Delete if !firing.cleanup => debug!("Cleanup disabled, keeping sent reminder"),
+        AfterFiring::Delete => {
+            if let Err(e) = delete_reminder(pool, id).await {
+                error!("Error deleting sent reminder: {:?}", e);
+            }
+        }
+    }
+}
+
+/// How `finish_reminder` follows up on a send.
+#[derive(Debug)]
+enum SendOutcome<'a> {
+    Delivered,
+    /// Retrying won't change what kind of channel it is.
+    Skipped(&'a SendReminderError),
+    Abandoned(&'a SendReminderError),
+    /// Back to pending and tried again from `retry_at`, unless it's out of
+    /// attempts.
+    Retry(&'a SendReminderError, DateTime<Utc>),
+}
+
+/// What to do with a reminder after trying to send it, counting the attempt
+/// in `counters` on the way.
+fn send_outcome<'a>(
+    sent: &'a Result<(), SendReminderError>,
+    trigger_time: DateTime<Utc>,
+    now: DateTime<Utc>,
+    firing: &FiringOptions,
+    counters: &Counters,
+) -> SendOutcome<'a> {
+    counters.record_send(sent);
+    match sent {
+        Ok(()) => SendOutcome::Delivered,
+        Err(e @ SendReminderError::UnsupportedChannel) => SendOutcome::Skipped(e),
+        Err(e) if past_max_age(trigger_time, now, firing.max_age) => SendOutcome::Abandoned(e),
+        Err(e) => SendOutcome::Retry(e, now + firing.retry_interval),
+    }
+}
+
+/// Whether a reminder still failing this long after its time should be given
+/// up on, the message would be stale by the time it got through.
+fn past_max_age(
+    trigger_time: DateTime<Utc>,
+    now: DateTime<Utc>,
+    max_age: Option<chrono::Duration>,
+) -> bool {
+    max_age.is_some_and(|max_age| now - trigger_time > max_age)
+}
+
+#[derive(Debug, PartialEq)]
+enum AfterFiring<'a> {
+    Delete,
+    Reschedule {
+        interval: &'a str,
+        remaining_occurrences: Option<i32>,
+    },
+}
+
+/// Recurring reminders go around again until they run out of occurrences,
+/// everything else is done with once sent.
+fn after_firing(reminder: &Reminder) -> AfterFiring<'_> {
+    let Some(interval) = &reminder.recurrence_interval else {
+        return AfterFiring::Delete;
+    };
+    match reminder.remaining_occurrences {
+        None => AfterFiring::Reschedule {
+            interval,
+            remaining_occurrences: None,
+        },
+        Some(remaining) if remaining > 1 => AfterFiring::Reschedule {
+            interval,
+            remaining_occurrences: Some(remaining - 1),
+        },
+        Some(_) => AfterFiring::Delete,
+    }
+}
+
+async fn schedule_next_occurrence(
+    pool: &PgPool,
+    id: i32,
+    trigger_time: DateTime<Utc>,
+    interval: &str,
+    timezone: Option<Tz>,
+    remaining_occurrences: Option<i32>,
+) {
+    let Some(next_time) = next_recurrence_after(interval, trigger_time, Utc::now(), timezone)
+    else {
+        // Left alone it would be due, and sent, on every check.
+        error!(
+            id,
+            interval, "Invalid recurrence interval, removing the reminder"
+        );
+        if let Err(e) = delete_reminder(pool, id).await {
+            error!("Error removing reminder {}: {:?}", id, e);
+        }
+        return;
+    };
+
+    if let Err(e) = set_next_trigger_time(pool, id, next_time, remaining_occurrences).await {
+        error!(id, "Error rescheduling reminder: {:?}", e);
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    dotenv::dotenv().ok();
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+        )
+        .init();
+    let config = match Config::from_env() {
+        Ok(config) => config,
+        Err(e) => {
+            error!("Invalid configuration: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    info!(
+        "Database pool: max {} connections, {} second acquire timeout",
+        config.db_max_connections,
+        config.db_acquire_timeout.as_secs()
+    );
+    let pool = match PgPoolOptions::new()
+        .max_connections(config.db_max_connections)
+        .acquire_timeout(config.db_acquire_timeout)
+        .connect(&config.db_url)
+        .await
+    {
+        Ok(pool) => pool,
+        Err(e) => {
+            error!(
+                "Couldn't connect to the database, check DATABASE_URL: {}",
+                e
+            );
+            std::process::exit(1);
+        }
+    };
+
+    if let Err(e) = sqlx::migrate!().run(&pool).await {
+        error!("Couldn't run database migrations: {}", e);
+        std::process::exit(1);
+    }
+    match reminder_table_columns(&pool).await {
+        Ok(columns) => {
+            let missing = missing_columns(REMINDER_COLUMNS, &columns);
+            if !missing.is_empty() {
+                error!(
+                    "The reminders table is missing columns {:?}, add a migration for them",
+                    missing
+                );
+                std::process::exit(1);
+            }
+        }
+        Err(e) => warn!("Error checking the database schema: {:?}", e),
+    }
+
+    let mut scheduler = AsyncScheduler::new();
+
+    let intents = gateway_intents(config.slash_only);
+    info!("Requesting gateway intents {:?}", intents);
+
+    let token = config.token.clone();
+    let http = Arc::new(Http::new(&token));
+    let shutdown_pool = pool.clone();
+
+    if let Some(metrics_addr) = config.metrics_addr.clone() {
+        tokio::spawn(serve_metrics(metrics_addr, pool.clone()));
+    }
+
+    // Everything that came due while the bot was down fires on the first
+    // check, optionally paced so it doesn't arrive as one burst.
+    let started_at = Utc::now();
+    let overdue = match count_overdue_reminders(&pool, started_at).await {
+        Ok(overdue) => overdue,
+        Err(e) => {
+            warn!("Error counting overdue reminders: {:?}", e);
+            0
+        }
+    };
+    let catch_up = overdue > 0 && config.catch_up_batch_size > 0;
+    if overdue > 0 {
+        warn!("{} reminders are overdue and will be sent now", overdue);
+    }
+    if catch_up {
+        info!(
+            "Sending overdue reminders {} at a time",
+            config.catch_up_batch_size
+        );
+    }
+
+    info!("Checking reminders every {} seconds", config.check_interval);
+    if config.cleanup {
+        info!(
+            "Sent reminders are marked deleted after firing and purged after {} days",
+            config.purge_after_days
+        );
+    } else {
+        warn!("Cleanup disabled, sent reminders are kept in the database");
+    }
+    register_jobs(
+        &mut scheduler,
+        &pool,
+        &http,
+        config.check_interval,
+        FiringOptions {
+            max_send_attempts: config.max_send_attempts,
+            retry_interval: chrono::Duration::seconds(config.retry_interval.into()),
+            max_age: config
+                .max_age_hours
+                .map(|hours| chrono::Duration::hours(hours.into())),
+            cleanup: config.cleanup,
+            reminder_template: config.reminder_template.as_deref().map(Arc::from),
+            embeds: config.reminder_embeds,
+            send_batch_size: config.send_batch_size,
+            catch_up_before: catch_up.then_some(started_at),
+            catch_up_batch_size: config.catch_up_batch_size,
+        },
+        config.purge_after_days,
+        config.digest_hour,
+    );
+
+    let bot = Handler {
+        pool: pool.clone(),
+        config,
+        last_reminder_set: Mutex::new(HashMap::new()),
+        reminder_lists: Mutex::new(HashMap::new()),
+        guild_settings: Mutex::new(HashMap::new()),
+    };
+
+    let (shutdown_tx, mut shutdown_rx) = watch::channel(false);
+    // `run_pending` only returns once the jobs it started have finished, so
+    // breaking between ticks never abandons a half-sent batch.
+    let scheduler_task = tokio::spawn(async move {
+        loop {
+            scheduler.run_pending().await;
+            tokio::select! {
+                _ = shutdown_rx.changed() => break,
+                _ = tokio::time::sleep(Duration::from_millis(100)) => {}
+            }
+        }
+    });
+
+    let mut client = match Client::builder(&token, intents).event_handler(bot).await {
+        Ok(client) => client,
+        Err(e) => {
+            error!(
+                "Couldn't create the Discord client, check DISCORD_TOKEN: {}",
+                e
+            );
+            std::process::exit(1);
+        }
+    };
+
+    let shard_manager = client.shard_manager.clone();
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        info!("Shutting down gracefully");
+
+        let _ = shutdown_tx.send(true);
+        if let Err(e) = scheduler_task.await {
+            error!("Scheduler task failed: {:?}", e);
+        }
+        shutdown_pool.close().await;
+        shard_manager.shutdown_all().await;
+    });
 
     if let Err(why) = client.start().await {
-        println!("Client error: {:?}", why);
+        error!("Client error: {:?}", why);
+        std::process::exit(1);
+    }
+}
+
+/// Text commands in servers need the privileged message content intent,
+/// slash-only mode leaves them out so the bot runs without it. Direct
+/// messages carry their content either way.
+fn gateway_intents(slash_only: bool) -> GatewayIntents {
+    if slash_only {
+        GatewayIntents::DIRECT_MESSAGES
+    } else {
+        GatewayIntents::GUILD_MESSAGES
+            | GatewayIntents::DIRECT_MESSAGES
+            | GatewayIntents::MESSAGE_CONTENT
+    }
+}
+
+/// Running totals since startup, served on `/metrics` next to the numbers
+/// read from the database.
+#[derive(Debug, Default)]
+struct Counters {
+    created: AtomicU64,
+    sent: AtomicU64,
+    failed: AtomicU64,
+}
+
+static COUNTERS: Counters = Counters {
+    created: AtomicU64::new(0),
+    sent: AtomicU64::new(0),
+    failed: AtomicU64::new(0),
+};
+
+impl Counters {
+    /// Every failed attempt counts, including ones that are retried later.
+    fn record_send(&self, sent: &Result<(), SendReminderError>) {
+        let counter = match sent {
+            Ok(()) => &self.sent,
+            Err(_) => &self.failed,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Prometheus' text exposition format.
+fn prometheus_metrics(metrics: &Metrics, counters: &Counters) -> String {
+    let mut out = String::new();
+    let mut push = |name: &str, kind: &str, help: &str, value: String| {
+        out.push_str(&format!(
+            "# HELP {name} {help}\n# TYPE {name} {kind}\n{name} {value}\n"
+        ));
+    };
+    push(
+        "reminders_pending",
+        "gauge",
+        "Reminders waiting to fire.",
+        metrics.pending.to_string(),
+    );
+    if let Some(next_trigger) = metrics.next_trigger {
+        push(
+            "reminders_next_trigger_timestamp_seconds",
+            "gauge",
+            "When the next pending reminder is due, as a Unix timestamp.",
+            next_trigger.timestamp().to_string(),
+        );
+    }
+    for (name, help, counter) in [
+        (
+            "reminders_created_total",
+            "Reminders set.",
+            &counters.created,
+        ),
+        (
+            "reminders_sent_total",
+            "Reminders delivered.",
+            &counters.sent,
+        ),
+        (
+            "reminders_failed_total",
+            "Reminder deliveries that failed.",
+            &counters.failed,
+        ),
+    ] {
+        push(
+            name,
+            "counter",
+            help,
+            counter.load(Ordering::Relaxed).to_string(),
+        );
+    }
+    out
+}
+
+async fn metrics_handler(
+    State(pool): State<PgPool>,
+) -> Result<([(header::HeaderName, &'static str); 1], String), StatusCode> {
+    match get_metrics(&pool).await {
+        Ok(metrics) => Ok((
+            [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+            prometheus_metrics(&metrics, &COUNTERS),
+        )),
+        Err(e) => {
+            error!("Error getting metrics: {:?}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn serve_metrics(addr: String, pool: PgPool) {
+    let app = Router::new()
+        .route("/health", get(|| async { StatusCode::OK }))
+        .route("/metrics", get(metrics_handler))
+        .with_state(pool);
+
+    let listener = match tokio::net::TcpListener::bind(&addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("Error binding metrics server to {}: {:?}", addr, e);
+            return;
+        }
+    };
+    info!("Serving metrics on {}", addr);
+    if let Err(e) = axum::serve(listener, app).await {
+        error!("Metrics server error: {:?}", e);
+    }
+}
+
+async fn shutdown_signal() {
+    let ctrl_c = tokio::signal::ctrl_c();
+
+    #[cfg(unix)]
+    {
+        let mut terminate =
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+                .expect("Failed to install SIGTERM handler");
+        tokio::select! {
+            _ = ctrl_c => {}
+            _ = terminate.recv() => {}
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = ctrl_c.await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use remindme_bot::parser::add_duration;
+    use serenity::all::{CommandData, GuildChannel, PrivateChannel};
+    use serenity::json::{from_value, json, to_value, Value};
+
+    use super::*;
+
+    /// Midnight as the default time, so dates on their own are easy to check.
+    fn test_config(vars: &[(&str, &str)]) -> Config {
+        Config::from_vars(|name| match name {
+            "DISCORD_TOKEN" => Some("token".to_string()),
+            "DATABASE_URL" => Some("postgres://localhost/remindme".to_string()),
+            "DEFAULT_REMINDER_TIME" => Some("00:00".to_string()),
+            _ => vars
+                .iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| value.to_string()),
+        })
+        .unwrap()
+    }
+
+    fn utc(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, day, hour, minute, 0)
+            .unwrap()
+    }
+
+    fn reminder(message_content: &str) -> Reminder {
+        Reminder {
+            id: None,
+            user_id: "1".to_string(),
+            target_user_id: "1".to_string(),
+            message_id: "2".to_string(),
+            message_content: message_content.to_string(),
+            trigger_time: utc(2024, 3, 6, 12, 0),
+            channel_id: "3".to_string(),
+            recurrence_interval: None,
+            status: STATUS_PENDING.to_string(),
+            attempts: 0,
+            deliver_dm: false,
+            remaining_occurrences: None,
+            created_at: None,
+            target_role_id: None,
+            priority: PRIORITY_NORMAL,
+            deleted_at: None,
+            tag: None,
+            retry_at: None,
+            source: None,
+        }
+    }
+
+    fn remindme_options(options: Value) -> CommandData {
+        from_value(json!({
+            "id": "1",
+            "name": "remindme",
+            "type": 1,
+            "options": options,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn reads_remindme_options() {
+        let data = remindme_options(json!([
+            {"name": "time", "type": 3, "value": "2h"},
+            {"name": "message", "type": 3, "value": "call mom"},
+        ]));
+        assert_eq!(
+            parse_remindme_options(&data.options()),
+            Some(("2h".to_string(), Some("call mom".to_string())))
+        );
+
+        // The message is optional and unknown options are ignored.
+        let data = remindme_options(json!([
+            {"name": "time", "type": 3, "value": "tomorrow"},
+            {"name": "colour", "type": 3, "value": "blue"},
+        ]));
+        assert_eq!(
+            parse_remindme_options(&data.options()),
+            Some(("tomorrow".to_string(), None))
+        );
+    }
+
+    #[test]
+    fn remindme_options_need_a_text_time() {
+        let no_time = remindme_options(json!([
+            {"name": "message", "type": 3, "value": "call mom"},
+        ]));
+        assert_eq!(parse_remindme_options(&no_time.options()), None);
+        assert_eq!(
+            parse_remindme_options(&remindme_options(json!([])).options()),
+            None
+        );
+
+        let wrong_types = remindme_options(json!([
+            {"name": "time", "type": 4, "value": 60},
+            {"name": "message", "type": 5, "value": true},
+        ]));
+        assert_eq!(parse_remindme_options(&wrong_types.options()), None);
+        let wrong_message = remindme_options(json!([
+            {"name": "time", "type": 3, "value": "1h"},
+            {"name": "message", "type": 4, "value": 5},
+        ]));
+        assert_eq!(
+            parse_remindme_options(&wrong_message.options()),
+            Some(("1h".to_string(), None))
+        );
+    }
+
+    #[tokio::test]
+    async fn fetches_each_id_once() {
+        let mut cache = HashMap::new();
+        let mut saved_calls = 0;
+        let mut fetched = Vec::new();
+        for id in [1, 2, 1, 1, 2, 3] {
+            let value = get_or_fetch(&mut cache, &mut saved_calls, id, || {
+                fetched.push(id);
+                async move { Ok::<_, ()>(id * 10) }
+            })
+            .await;
+            assert_eq!(value, Ok(id * 10));
+        }
+
+        assert_eq!(fetched, vec![1, 2, 3]);
+        assert_eq!(saved_calls, 3);
+    }
+
+    #[tokio::test]
+    async fn failed_fetches_are_retried() {
+        let mut cache = HashMap::new();
+        let mut saved_calls = 0;
+        let failed: Result<i32, &str> =
+            get_or_fetch(&mut cache, &mut saved_calls, 1, || async { Err("503") }).await;
+        assert_eq!(failed, Err("503"));
+
+        let retried = get_or_fetch(&mut cache, &mut saved_calls, 1, || async {
+            Ok::<_, &str>(10)
+        })
+        .await;
+        assert_eq!(retried, Ok(10));
+        assert_eq!(saved_calls, 0);
+    }
+
+    #[tokio::test]
+    async fn only_a_failed_first_part_fails_the_send() {
+        let parts: Vec<String> = ["one", "two", "three"].map(String::from).to_vec();
+        // Parts already out would be repeated by a retry, so once the first
+        // is through it counts as sent.
+        for (failing, expected, expected_sent) in [
+            ("one", Err("503"), vec!["one"]),
+            ("two", Ok(()), vec!["one", "two"]),
+            ("none", Ok(()), vec!["one", "two", "three"]),
+        ] {
+            let mut sent = Vec::new();
+            let result = send_parts(&parts, |part| {
+                sent.push(part);
+                async move {
+                    if part == failing {
+                        Err("503")
+                    } else {
+                        Ok(())
+                    }
+                }
+            })
+            .await;
+            assert_eq!(result, expected);
+            assert_eq!(sent, expected_sent);
+        }
+    }
+
+    #[test]
+    fn retries_back_off_exponentially() {
+        let delays: Vec<u64> = (0..MAX_SEND_RETRIES)
+            .map(|retry| retry_delay(retry).as_secs())
+            .collect();
+        assert_eq!(delays, vec![1, 2, 4]);
+    }
+
+    #[test]
+    fn only_transient_errors_are_retried() {
+        for status in [429, 500, 502, 503, 504] {
+            assert!(is_retryable_status(status), "{}", status);
+        }
+        for status in [400, 401, 403, 404] {
+            assert!(!is_retryable_status(status), "{}", status);
+        }
+    }
+
+    #[tokio::test]
+    async fn one_broken_reminder_does_not_stop_the_batch() {
+        let mut fired = Vec::new();
+        for id in 1..=3 {
+            isolate(&[id], async {
+                if id == 2 {
+                    panic!("reminder {} is broken", id);
+                }
+                fired.push(id);
+            })
+            .await;
+        }
+        assert_eq!(fired, vec![1, 3]);
+    }
+
+    #[tokio::test]
+    async fn malformed_ids_fail_before_calling_discord() {
+        let http = Http::new("");
+        let mut cache = DiscordCache::default();
+        let bad_user = Reminder {
+            user_id: "not a snowflake".to_string(),
+            ..reminder("stretch")
+        };
+        let bad_channel = Reminder {
+            channel_id: "not a snowflake".to_string(),
+            ..reminder("stretch")
+        };
+        let cases = [
+            (bad_user, SendReminderError::InvalidUserId),
+            (bad_channel, SendReminderError::InvalidChannelId),
+        ];
+        for (reminder, expected) in cases {
+            let error = send_reminders(&http, &mut cache, None, false, &[reminder])
+                .await
+                .unwrap_err();
+            assert_eq!(error.to_string(), expected.to_string());
+        }
+    }
+
+    #[test]
+    fn resolved_times_are_shown_without_saving() {
+        let now = utc(2024, 3, 6, 12, 0);
+        let trigger_time = utc(2024, 3, 8, 9, 0);
+        assert_eq!(
+            resolved_time_message(trigger_time, None, now),
+            "That's 2024-03-08 09:00 UTC (in about 2 days), nothing was saved"
+        );
+        assert_eq!(
+            resolved_time_message(trigger_time, Some(chrono_tz::Europe::Warsaw), now),
+            "That's 2024-03-08 10:00 CET (in about 2 days), nothing was saved"
+        );
+    }
+
+    #[test]
+    fn digests_go_out_once_during_the_digest_hour() {
+        let subscriber = |last_sent_on, timezone| DigestSubscriber {
+            user_id: "1".to_string(),
+            last_sent_on,
+            timezone,
+        };
+        let today = NaiveDate::from_ymd_opt(2024, 3, 6).unwrap();
+        let yesterday = NaiveDate::from_ymd_opt(2024, 3, 5).unwrap();
+
+        assert_eq!(
+            digest_due(&subscriber(None, None), 8, utc(2024, 3, 6, 8, 20)),
+            Some(today)
+        );
+        assert_eq!(
+            digest_due(&subscriber(Some(yesterday), None), 8, utc(2024, 3, 6, 8, 0)),
+            Some(today)
+        );
+        assert_eq!(
+            digest_due(&subscriber(Some(today), None), 8, utc(2024, 3, 6, 8, 50)),
+            None
+        );
+        assert_eq!(
+            digest_due(&subscriber(None, None), 8, utc(2024, 3, 6, 9, 0)),
+            None
+        );
+        // 08:00 in Warsaw is 07:00 UTC in winter.
+        let warsaw = Some(chrono_tz::Europe::Warsaw);
+        assert_eq!(
+            digest_due(&subscriber(None, warsaw), 8, utc(2024, 3, 6, 7, 10)),
+            Some(today)
+        );
+        assert_eq!(
+            digest_due(&subscriber(None, warsaw), 8, utc(2024, 3, 6, 8, 10)),
+            None
+        );
+    }
+
+    #[test]
+    fn digests_group_reminders_per_subscriber() {
+        let set_by = |user_id: &str, message_content: &str| Reminder {
+            user_id: user_id.to_string(),
+            ..reminder(message_content)
+        };
+        let reminders = [
+            set_by("1", "stretch"),
+            set_by("2", "standup"),
+            set_by("3", "not subscribed"),
+            set_by("1", "call mom"),
+        ];
+
+        let groups = group_by_user(&["1", "2", "4"], &reminders);
+        let contents = |user_id| {
+            groups.get(user_id).map(|reminders: &Vec<&Reminder>| {
+                reminders
+                    .iter()
+                    .map(|reminder| reminder.message_content.as_str())
+                    .collect::<Vec<_>>()
+            })
+        };
+        assert_eq!(contents("1"), Some(vec!["stretch", "call mom"]));
+        assert_eq!(contents("2"), Some(vec!["standup"]));
+        assert_eq!(contents("3"), None);
+        assert_eq!(contents("4"), None);
+
+        assert_eq!(
+            format_digest(&groups["2"], None),
+            "Your reminders for the next 24 hours:\n- 2024-03-06 12:00 UTC - standup\n"
+        );
+    }
+
+    #[test]
+    fn upcoming_reminders_are_summarised_per_hour() {
+        assert_eq!(
+            format_upcoming(24, &[]),
+            "No reminders due in the next 24 hours"
+        );
+        assert_eq!(
+            format_upcoming(
+                24,
+                &[(utc(2024, 3, 6, 14, 0), 3), (utc(2024, 3, 7, 9, 0), 1)]
+            ),
+            "4 reminders due in the next 24 hours:\n2024-03-06 14:00 UTC: 3\n2024-03-07 09:00 UTC: 1"
+        );
+    }
+
+    #[test]
+    fn undeliverable_reminders_are_abandoned_once_too_old() {
+        let trigger_time = utc(2024, 3, 6, 12, 0);
+        let day = Some(chrono::Duration::hours(24));
+        assert!(!past_max_age(trigger_time, utc(2024, 3, 6, 12, 1), day));
+        assert!(!past_max_age(trigger_time, utc(2024, 3, 7, 12, 0), day));
+        assert!(past_max_age(trigger_time, utc(2024, 3, 7, 12, 1), day));
+        // Without a limit only the attempt count gives up on it.
+        assert!(!past_max_age(trigger_time, utc(2025, 3, 6, 12, 0), None));
+    }
+
+    #[test]
+    fn messages_from_the_bot_itself_are_dropped() {
+        let bot_id = UserId::new(9);
+        let author = |id, bot| {
+            let mut user = User::default();
+            user.id = UserId::new(id);
+            user.bot = bot;
+            user
+        };
+        assert!(is_ignored_author(&author(9, true), bot_id));
+        assert!(is_ignored_author(&author(9, false), bot_id));
+        assert!(is_ignored_author(&author(5, true), bot_id));
+        assert!(!is_ignored_author(&author(5, false), bot_id));
+    }
+
+    #[test]
+    fn reminders_can_be_sent_as_embeds() {
+        let embed = reminder_embed(
+            "water the plants",
+            Some("https://discord.com/channels/1/3/2"),
+            Some(utc(2024, 3, 6, 12, 0)),
+            Some("https://cdn.discordapp.com/avatars/1/a.png".to_string()),
+        );
+        let embed = to_value(&embed).unwrap();
+        assert_eq!(embed["title"], "Reminder");
+        assert_eq!(embed["description"], "water the plants");
+        assert_eq!(embed["url"], "https://discord.com/channels/1/3/2");
+        assert_eq!(
+            embed["fields"],
+            json!([{"name": "Set", "value": "2024-03-06 12:00 UTC", "inline": true}])
+        );
+        assert_eq!(
+            embed["thumbnail"]["url"],
+            "https://cdn.discordapp.com/avatars/1/a.png"
+        );
+
+        let bare = to_value(reminder_embed("", None, None, None)).unwrap();
+        assert_eq!(
+            bare["description"],
+            "You didn't leave a message with this one"
+        );
+        assert_eq!(bare.get("url"), None);
+        assert_eq!(bare.get("thumbnail"), None);
+    }
+
+    #[test]
+    fn malformed_ids_are_caught_per_reminder() {
+        assert!(malformed_id(&reminder("stretch")).is_none());
+        let with_role = Reminder {
+            target_role_id: Some("7".to_string()),
+            ..reminder("standup")
+        };
+        assert!(malformed_id(&with_role).is_none());
+
+        for bad in ["", "abc", "0", "-1", "12.5", "99999999999999999999"] {
+            let cases = [
+                (
+                    Reminder {
+                        user_id: bad.to_string(),
+                        ..reminder("stretch")
+                    },
+                    SendReminderError::InvalidUserId,
+                ),
+                (
+                    Reminder {
+                        target_user_id: bad.to_string(),
+                        ..reminder("stretch")
+                    },
+                    SendReminderError::InvalidUserId,
+                ),
+                (
+                    Reminder {
+                        channel_id: bad.to_string(),
+                        ..reminder("stretch")
+                    },
+                    SendReminderError::InvalidChannelId,
+                ),
+                (
+                    Reminder {
+                        target_role_id: Some(bad.to_string()),
+                        ..reminder("stretch")
+                    },
+                    SendReminderError::InvalidRoleId,
+                ),
+            ];
+            for (reminder, expected) in cases {
+                let error = malformed_id(&reminder).unwrap();
+                assert_eq!(error.to_string(), expected.to_string(), "{:?}", bad);
+            }
+        }
+    }
+
+    #[test]
+    fn confirms_reminders_in_the_users_timezone() {
+        let now = utc(2024, 3, 6, 12, 0);
+        let trigger_time = utc(2024, 3, 6, 15, 0);
+
+        assert_eq!(
+            reminder_set_message(trigger_time, None, now, DEFAULT_LOCALE),
+            "Reminder set for 2024-03-06 15:00 UTC (in about 3 hours)"
+        );
+        assert_eq!(
+            reminder_set_message(
+                trigger_time,
+                Some(chrono_tz::Europe::Warsaw),
+                now,
+                DEFAULT_LOCALE
+            ),
+            "Reminder set for 2024-03-06 16:00 CET (in about 3 hours)"
+        );
+    }
+
+    #[test]
+    fn formats_time_until() {
+        assert_eq!(
+            format_relative(chrono::Duration::seconds(20)),
+            "in less than a minute"
+        );
+        assert_eq!(
+            format_relative(chrono::Duration::minutes(45)),
+            "in about 45 minutes"
+        );
+        assert_eq!(
+            format_relative(chrono::Duration::hours(1)),
+            "in about 1 hour"
+        );
+        // Set a moment ago, so slightly under a full day.
+        assert_eq!(
+            format_relative(chrono::Duration::days(1) - chrono::Duration::seconds(2)),
+            "in about 1 day"
+        );
+        assert_eq!(
+            format_relative(chrono::Duration::weeks(2)),
+            "in about 14 days"
+        );
+    }
+
+    fn guild_channel(kind: ChannelType) -> Channel {
+        let mut channel = GuildChannel::default();
+        channel.kind = kind;
+        Channel::Guild(channel)
+    }
+
+    #[test]
+    fn reminders_reach_dms_as_well_as_server_channels() {
+        assert_eq!(
+            reminder_destination(&Channel::Private(PrivateChannel::default())),
+            Some(ReminderDestination::Private)
+        );
+        assert_eq!(
+            reminder_destination(&guild_channel(ChannelType::Text)),
+            Some(ReminderDestination::GuildText)
+        );
+        assert_eq!(
+            reminder_destination(&guild_channel(ChannelType::Category)),
+            None
+        );
+    }
+
+    #[test]
+    fn reminders_go_to_channels_with_a_chat() {
+        for kind in [ChannelType::Text, ChannelType::News, ChannelType::Voice] {
+            assert_eq!(
+                reminder_destination(&guild_channel(kind)),
+                Some(ReminderDestination::GuildText)
+            );
+        }
+        for kind in [ChannelType::PublicThread, ChannelType::PrivateThread] {
+            assert_eq!(
+                reminder_destination(&guild_channel(kind)),
+                Some(ReminderDestination::Thread)
+            );
+        }
+        for kind in [ChannelType::Forum, ChannelType::Unknown(99)] {
+            assert_eq!(reminder_destination(&guild_channel(kind)), None);
+        }
+    }
+
+    #[test]
+    fn limit_reply_uses_the_servers_prefix() {
+        let config = test_config(&[("COMMAND_PREFIX", "!")]);
+        let settings = resolve_settings(
+            &config,
+            GuildSettings {
+                prefix: Some("?".to_string()),
+                max_reminders_per_user: None,
+            },
+        );
+        assert!(reminder_limit_message(&settings.prefix).contains("`?cancel`"));
+        assert!(
+            invalid_date_message("someday", &settings.prefix, DEFAULT_LOCALE)
+                .contains("`?remindme 1d water the plants`")
+        );
+    }
+
+    #[test]
+    fn server_settings_override_the_global_ones() {
+        let config = test_config(&[("COMMAND_PREFIX", "!"), ("MAX_REMINDERS_PER_USER", "50")]);
+        assert_eq!(
+            resolve_settings(&config, GuildSettings::default()),
+            ServerSettings {
+                prefix: "!".to_string(),
+                max_reminders_per_user: 50,
+            }
+        );
+        assert_eq!(
+            resolve_settings(
+                &config,
+                GuildSettings {
+                    prefix: Some("?".to_string()),
+                    max_reminders_per_user: None,
+                }
+            ),
+            ServerSettings {
+                prefix: "?".to_string(),
+                max_reminders_per_user: 50,
+            }
+        );
+        assert_eq!(
+            resolve_settings(
+                &config,
+                GuildSettings {
+                    prefix: None,
+                    max_reminders_per_user: Some(5),
+                }
+            ),
+            ServerSettings {
+                prefix: "!".to_string(),
+                max_reminders_per_user: 5,
+            }
+        );
+    }
+
+    #[test]
+    fn slash_only_mode_drops_the_message_intents() {
+        let intents = gateway_intents(false);
+        assert!(intents.contains(GatewayIntents::GUILD_MESSAGES | GatewayIntents::MESSAGE_CONTENT));
+
+        let intents = gateway_intents(true);
+        assert!(
+            !intents.intersects(GatewayIntents::GUILD_MESSAGES | GatewayIntents::MESSAGE_CONTENT)
+        );
+        assert!(intents.contains(GatewayIntents::DIRECT_MESSAGES));
+    }
+
+    #[test]
+    fn metrics_are_in_prometheus_format() {
+        let counters = Counters::default();
+        counters.created.fetch_add(3, Ordering::Relaxed);
+        let metrics = Metrics {
+            pending: 2,
+            next_trigger: Some(utc(2024, 3, 6, 12, 0)),
+        };
+        let text = prometheus_metrics(&metrics, &counters);
+        assert!(text.starts_with(
+            "# HELP reminders_pending Reminders waiting to fire.\n# TYPE reminders_pending gauge\nreminders_pending 2\n"
+        ));
+        assert!(text.contains("\nreminders_next_trigger_timestamp_seconds 1709726400\n"));
+        assert!(
+            text.contains("# TYPE reminders_created_total counter\nreminders_created_total 3\n")
+        );
+        assert!(text.ends_with("\nreminders_failed_total 0\n"));
+
+        let idle = Metrics {
+            pending: 0,
+            next_trigger: None,
+        };
+        assert!(!prometheus_metrics(&idle, &counters).contains("next_trigger"));
+    }
+
+    #[test]
+    fn sending_counts_reminders_sent_and_failed() {
+        let now = utc(2024, 3, 6, 12, 0);
+        let firing = FiringOptions {
+            max_send_attempts: 5,
+            retry_interval: chrono::Duration::minutes(1),
+            max_age: Some(chrono::Duration::hours(1)),
+            cleanup: true,
+            reminder_template: None,
+            embeds: false,
+            send_batch_size: 0,
+            catch_up_before: None,
+            catch_up_batch_size: 5,
+        };
+        let counters = Counters::default();
+        let outcome = |sent, trigger_time| {
+            format!(
+                "{:?}",
+                send_outcome(&sent, trigger_time, now, &firing, &counters)
+            )
+        };
+
+        assert_eq!(outcome(Ok(()), now), "Delivered");
+        assert_eq!(outcome(Ok(()), now), "Delivered");
+        assert_eq!(
+            outcome(Err(SendReminderError::ChannelNotFound), now),
+            format!("Retry(ChannelNotFound, {:?})", utc(2024, 3, 6, 12, 1))
+        );
+        assert_eq!(
+            outcome(
+                Err(SendReminderError::ChannelNotFound),
+                now - chrono::Duration::hours(2)
+            ),
+            "Abandoned(ChannelNotFound)"
+        );
+        assert_eq!(
+            outcome(Err(SendReminderError::UnsupportedChannel), now),
+            "Skipped(UnsupportedChannel)"
+        );
+        assert_eq!(counters.sent.load(Ordering::Relaxed), 2);
+        assert_eq!(counters.failed.load(Ordering::Relaxed), 3);
+        assert!(prometheus_metrics(
+            &Metrics {
+                pending: 0,
+                next_trigger: None
+            },
+            &counters
+        )
+        .contains("\nreminders_sent_total 2\n"));
+    }
+
+    #[test]
+    fn reminders_link_back_when_the_message_still_exists() {
+        let target = Mention::User(UserId::new(1));
+
+        assert_eq!(
+            build_reminder_message(
+                target,
+                UserId::new(1),
+                "stretch",
+                Some("https://discord.com/channels/1/2/3"),
+                None
+            ),
+            "Hey <@1>, you asked me to remind you about this: stretch reference message: https://discord.com/channels/1/2/3"
+        );
+        // Deleted reference messages just drop the link.
+        assert_eq!(
+            build_reminder_message(target, UserId::new(2), "stretch", None, None),
+            "Hey <@1>, <@2> asked me to remind you about this: stretch"
+        );
+    }
+
+    #[test]
+    fn replies_in_the_users_language() {
+        let now = utc(2024, 3, 6, 12, 0);
+        assert_eq!(
+            reminder_set_message(utc(2024, 3, 6, 14, 0), None, now, "pl"),
+            "Przypomnienie ustawione na 2024-03-06 14:00 UTC (in about 2 hours)"
+        );
+        assert_eq!(
+            format_reminder_list(&[], None, None, 0, "de"),
+            "Du hast keine ausstehenden Erinnerungen"
+        );
+        assert!(invalid_date_message("someday", "!", "xx")
+            .starts_with("I couldn't understand the date"));
+    }
+
+    #[test]
+    fn sending_pauses_between_batches() {
+        let pauses: Vec<usize> = (0..7).filter(|&index| pause_before(index, 3)).collect();
+        assert_eq!(pauses, vec![3, 6]);
+        assert!((0..100).all(|index| !pause_before(index, 0)));
+        assert!(!pause_before(0, 1));
+        assert!(pause_before(1, 1));
+    }
+
+    #[test]
+    fn overdue_reminders_from_before_startup_are_paced() {
+        let started_at = utc(2024, 3, 6, 12, 30);
+        let firing = |send_batch_size, catch_up_before| FiringOptions {
+            max_send_attempts: 5,
+            retry_interval: chrono::Duration::minutes(1),
+            max_age: None,
+            cleanup: true,
+            reminder_template: None,
+            embeds: false,
+            send_batch_size,
+            catch_up_before,
+            catch_up_batch_size: 5,
+        };
+        let overdue = [reminder("stretch")];
+        let mut on_time = reminder("drink water");
+        on_time.trigger_time = started_at + chrono::Duration::minutes(1);
+        let on_time = [on_time];
+
+        assert_eq!(batch_size_for(&firing(0, Some(started_at)), &overdue), 5);
+        assert_eq!(batch_size_for(&firing(3, Some(started_at)), &overdue), 3);
+        assert_eq!(batch_size_for(&firing(10, Some(started_at)), &overdue), 5);
+        // Caught up, or nothing was overdue at startup.
+        assert_eq!(batch_size_for(&firing(0, Some(started_at)), &on_time), 0);
+        assert_eq!(batch_size_for(&firing(10, None), &overdue), 10);
+    }
+
+    #[test]
+    fn long_reminders_are_split_under_the_limit() {
+        assert_eq!(split_message("stretch", 2000), vec!["stretch"]);
+        assert_eq!(split_message("", 2000), vec![""]);
+        assert_eq!(
+            split_message("water the plants", 10),
+            vec!["water the", "plants"]
+        );
+        assert_eq!(
+            split_message("first line\nsecond line", 15),
+            vec!["first line", "second line"]
+        );
+        // Without anywhere to break, the text is cut mid-word.
+        assert_eq!(split_message("ąąąąąąąąąą", 4), vec!["ąąąą", "ąąąą", "ąą"]);
+
+        let long = format!(
+            "Hey <@1>, you asked me to remind you about this: {}",
+            "word ".repeat(500)
+        );
+        let parts = split_message(long.trim_end(), DISCORD_MESSAGE_LIMIT);
+        assert_eq!(parts.len(), 2);
+        assert!(parts
+            .iter()
+            .all(|part| part.chars().count() <= DISCORD_MESSAGE_LIMIT));
+        assert_eq!(parts.join(" "), long.trim_end());
+    }
+
+    #[test]
+    fn splits_help_to_fit_discord_messages() {
+        let help = help_message("!");
+        assert!(help.chars().count() > DISCORD_MESSAGE_LIMIT);
+        let parts = split_message(&help, DISCORD_MESSAGE_LIMIT);
+        assert!(parts
+            .iter()
+            .all(|part| part.chars().count() <= DISCORD_MESSAGE_LIMIT));
+        assert_eq!(parts.join(" "), help.trim_end());
+    }
+
+    #[test]
+    fn reminder_templates_fill_in_placeholders() {
+        let target = Mention::User(UserId::new(1));
+        let link = Some("https://discord.com/channels/1/2/3");
+
+        assert_eq!(
+            render_reminder_template("{user}: {message} ({link})", target, "stretch", link),
+            "<@1>: stretch (https://discord.com/channels/1/2/3)"
+        );
+        // Placeholders can be left out or repeated, unknown ones stay as written.
+        assert_eq!(
+            render_reminder_template("Psst {user} {user}, {when}", target, "stretch", link),
+            "Psst <@1> <@1>, {when}"
+        );
+        assert_eq!(
+            render_reminder_template("{message} {link}", target, "stretch", None),
+            "stretch "
+        );
+        assert_eq!(
+            render_reminder_template("{message}", target, "say {user}", None),
+            "say {user}"
+        );
+    }
+
+    #[test]
+    fn role_reminders_ping_the_role() {
+        let target = Mention::Role(RoleId::new(7));
+        assert_eq!(
+            build_reminder_message(target, UserId::new(1), "standup", None, None),
+            "Hey <@&7>, <@1> asked me to remind you about this: standup"
+        );
+        assert_eq!(
+            render_reminder_template("{user}: {message}", target, "standup", None),
+            "<@&7>: standup"
+        );
+
+        assert!(may_mention_role(true, Permissions::empty()));
+        assert!(may_mention_role(false, Permissions::MENTION_EVERYONE));
+        assert!(!may_mention_role(false, Permissions::SEND_MESSAGES));
+    }
+
+    #[test]
+    fn reminders_say_when_they_were_set() {
+        let target = Mention::User(UserId::new(1));
+
+        assert_eq!(
+            build_reminder_message(target, UserId::new(1), "stretch", None, Some("3 days ago")),
+            "Hey <@1>, 3 days ago you asked me to remind you about this: stretch"
+        );
+        assert_eq!(
+            build_reminder_message(target, UserId::new(2), "stretch", None, Some("1 hour ago")),
+            "Hey <@1>, 1 hour ago <@2> asked me to remind you about this: stretch"
+        );
+    }
+
+    #[test]
+    fn describes_how_long_ago_in_the_largest_unit() {
+        let ago = |seconds| time_ago(chrono::Duration::seconds(seconds));
+        assert_eq!(ago(20), "a moment ago");
+        assert_eq!(ago(60), "1 minute ago");
+        assert_eq!(ago(2 * 60 * 60 + 59 * 60), "2 hours ago");
+        assert_eq!(ago(3 * 24 * 60 * 60), "3 days ago");
+        assert_eq!(ago(14 * 24 * 60 * 60), "2 weeks ago");
+        assert_eq!(ago(45 * 24 * 60 * 60), "1 month ago");
+        assert_eq!(ago(800 * 24 * 60 * 60), "2 years ago");
+        // Clock skew between the bot and the database.
+        assert_eq!(ago(-5), "a moment ago");
+    }
+
+    #[test]
+    fn groups_reminders_for_the_same_person_and_place() {
+        let in_channel = |channel_id: &str, target_user_id: &str, content: &str| Reminder {
+            channel_id: channel_id.to_string(),
+            target_user_id: target_user_id.to_string(),
+            ..reminder(content)
+        };
+        let by_dm = Reminder {
+            deliver_dm: true,
+            ..reminder("by dm")
+        };
+        let groups = group_reminders(vec![
+            in_channel("3", "1", "stretch"),
+            in_channel("4", "1", "elsewhere"),
+            in_channel("3", "5", "someone else"),
+            by_dm,
+            in_channel("3", "1", "drink water"),
+        ]);
+        let contents: Vec<Vec<&str>> = groups
+            .iter()
+            .map(|group| {
+                group
+                    .iter()
+                    .map(|reminder| reminder.message_content.as_str())
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            contents,
+            vec![
+                vec!["stretch", "drink water"],
+                vec!["elsewhere"],
+                vec!["someone else"],
+                vec!["by dm"],
+            ]
+        );
+    }
+
+    #[test]
+    fn long_reminders_are_split_across_messages() {
+        let long = "a".repeat(1000);
+        let groups = group_reminders(vec![reminder(&long), reminder(&long), reminder("short")]);
+        let sizes: Vec<usize> = groups.iter().map(Vec::len).collect();
+        assert_eq!(sizes, vec![2, 1]);
+    }
+
+    #[test]
+    fn grouped_reminders_mention_the_target_once() {
+        let target = Mention::User(UserId::new(1));
+        let entries = [
+            ReminderEntry {
+                creator_id: UserId::new(1),
+                content: "stretch",
+                reference_link: Some("https://discord.com/channels/1/2/3".to_string()),
+                set_ago: Some("2 hours ago".to_string()),
+            },
+            ReminderEntry {
+                creator_id: UserId::new(2),
+                content: "call back",
+                reference_link: None,
+                set_ago: Some("1 day ago".to_string()),
+            },
+            ReminderEntry {
+                creator_id: UserId::new(2),
+                content: "old one",
+                reference_link: None,
+                set_ago: None,
+            },
+        ];
+        assert_eq!(
+            build_grouped_reminder_message(target, &entries),
+            "Hey <@1>, you asked me to remind you about these:\n- stretch (2 hours ago) reference message: https://discord.com/channels/1/2/3\n- call back (from <@2>, 1 day ago)\n- old one (from <@2>)\n"
+        );
+    }
+
+    #[test]
+    fn truncate_keeps_short_text() {
+        assert_eq!(truncate("water the plants", 16), "water the plants");
+    }
+
+    #[test]
+    fn truncate_cuts_on_char_boundaries() {
+        assert_eq!(truncate("zażółć gęślą jaźń", 6), "zażółć...");
+        assert_eq!(truncate("🎉🎉🎉", 2), "🎉🎉...");
+    }
+
+    #[test]
+    fn long_messages_are_cut_to_the_limit() {
+        assert_eq!(
+            limit_length("water the plants", 16),
+            ("water the plants".to_string(), false)
+        );
+        assert_eq!(
+            limit_length("water the plants", 10),
+            ("water t...".to_string(), true)
+        );
+        // Multi-byte characters are kept whole.
+        let (cut, truncated) = limit_length("zażółć gęślą jaźń", 9);
+        assert_eq!(cut, "zażółć...");
+        assert!(truncated);
+        let (cut, _) = limit_length(&"🎉".repeat(1600), 1500);
+        assert_eq!(cut.chars().count(), 1500);
+        assert!(cut.ends_with("🎉..."));
+    }
+
+    #[test]
+    fn lists_reminders_with_numbers() {
+        assert_eq!(
+            format_reminder_list(&[], None, None, 0, DEFAULT_LOCALE),
+            "You have no pending reminders"
+        );
+
+        let list = format_reminder_list(
+            &[reminder("water the plants"), reminder("call mom")],
+            None,
+            None,
+            0,
+            DEFAULT_LOCALE,
+        );
+        assert_eq!(
+            list,
+            "Your pending reminders:\n1. 2024-03-06 12:00 UTC - water the plants\n2. 2024-03-06 12:00 UTC - call mom\n"
+        );
+    }
+
+    #[test]
+    fn lists_can_be_filtered_by_tag() {
+        let tagged = |message_content: &str, tag: Option<&str>| Reminder {
+            tag: tag.map(str::to_string),
+            ..reminder(message_content)
+        };
+        let reminders = [
+            tagged("send the report", Some("work")),
+            tagged("call mom", None),
+            tagged("standup", Some("work")),
+            tagged("water the plants", Some("home")),
+        ];
+
+        let numbers = |tag| {
+            tagged_reminders(&reminders, tag)
+                .into_iter()
+                .map(|(number, _)| number)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(numbers(None), vec![1, 2, 3, 4]);
+        assert_eq!(numbers(Some("work")), vec![1, 3]);
+        assert_eq!(numbers(Some("gym")), Vec::<usize>::new());
+
+        assert_eq!(
+            format_reminder_list(&reminders, Some("work"), None, 0, DEFAULT_LOCALE),
+            "Your pending reminders tagged #work:\n1. 2024-03-06 12:00 UTC - send the report #work\n3. 2024-03-06 12:00 UTC - standup #work\n"
+        );
+        assert_eq!(
+            format_reminder_list(&reminders, Some("gym"), None, 0, DEFAULT_LOCALE),
+            "You have no pending reminders tagged #gym"
+        );
+    }
+
+    #[test]
+    fn long_lists_are_split_into_pages() {
+        let numbers: Vec<usize> = (1..=25).collect();
+        assert_eq!(page_of(&numbers, 0), (0, &numbers[0..10]));
+        assert_eq!(page_of(&numbers, 2), (2, &numbers[20..25]));
+        // Past the end shows the last page.
+        assert_eq!(page_of(&numbers, 7), (2, &numbers[20..25]));
+        assert_eq!(page_of::<usize>(&[], 1), (0, &[][..]));
+        assert_eq!(page_count(25), 3);
+        assert_eq!(page_count(10), 1);
+        assert_eq!(page_count(0), 1);
+
+        let reminders: Vec<Reminder> = (0..25).map(|_| reminder("stretch")).collect();
+        let list = format_reminder_list(&reminders, None, None, 1, DEFAULT_LOCALE);
+        assert!(list.contains("11. "));
+        assert!(list.contains("20. "));
+        assert!(!list.contains("21. "));
+        assert!(list.ends_with("Page 2 of 3\n"));
+    }
+
+    #[test]
+    fn listed_content_cant_ping_anyone() {
+        let list = format_reminder_list(
+            &[reminder("@everyone standup")],
+            None,
+            None,
+            0,
+            DEFAULT_LOCALE,
+        );
+        assert!(!list.contains("@everyone"));
+    }
+
+    #[test]
+    fn echoed_reminders_cant_ping_anyone() {
+        let summary = reminder_summary(
+            "Cancelled reminder 1 set for",
+            &reminder("@everyone standup"),
+            None,
+        );
+        assert!(summary.starts_with("Cancelled reminder 1 set for 2024-03-06 12:00 UTC: "));
+        assert!(!summary.contains("@everyone"));
+    }
+
+    #[test]
+    fn cancels_all_reminders_on_request() {
+        assert!(is_cancel_all("!remindme cancel all", "!"));
+        assert!(is_cancel_all(" !RemindMe Cancel  ALL ", "!"));
+        assert!(!is_cancel_all("!remindme cancel all tomorrow", "!"));
+        assert!(!is_cancel_all("!remindme 1d cancel all", "!"));
+        assert_eq!(
+            command_name("!clearreminders", "!").as_deref(),
+            Some("clearreminders")
+        );
+    }
+
+    #[test]
+    fn exports_reminders_in_the_users_timezone() {
+        assert!(is_export_command("!remindme export", "!"));
+        assert!(is_export_command(" !RemindMe EXPORT ", "!"));
+        assert!(!is_export_command("!remindme export 1d", "!"));
+
+        let recurring = Reminder {
+            recurrence_interval: Some("1w".to_string()),
+            ..reminder("standup")
+        };
+        let reminders = [reminder("stretch"), recurring];
+        assert_eq!(
+            to_value(export_reminders(
+                &reminders,
+                Some(chrono_tz::Europe::Warsaw)
+            ))
+            .unwrap(),
+            json!([
+                {"time": "2024-03-06 13:00", "timezone": "Europe/Warsaw", "message": "stretch"},
+                {
+                    "time": "2024-03-06 13:00",
+                    "timezone": "Europe/Warsaw",
+                    "message": "standup",
+                    "every": "1w"
+                },
+            ])
+        );
+        assert_eq!(
+            to_value(export_reminders(&reminders[..1], None)).unwrap(),
+            json!([{"time": "2024-03-06 12:00", "timezone": "UTC", "message": "stretch"}])
+        );
+    }
+
+    #[test]
+    fn import_files_are_json_lists_of_time_and_message() {
+        assert!(is_import_command("!remindme import", "!"));
+        assert!(is_import_command(" !RemindMe IMPORT ", "!"));
+        assert!(!is_import_command("!remindme import 1d", "!"));
+
+        let rows = parse_import(
+            br#"[{"time": "1d", "message": "stretch"}, {"time": "2024-03-07 09:00"}]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                ImportRow {
+                    time: "1d".to_string(),
+                    message: "stretch".to_string(),
+                    ..ImportRow::default()
+                },
+                ImportRow {
+                    time: "2024-03-07 09:00".to_string(),
+                    ..ImportRow::default()
+                },
+            ]
+        );
+        assert!(parse_import(br#"{"time": "1d"}"#).is_none());
+        assert!(parse_import(br#"[{"message": "no time"}]"#).is_none());
+        assert!(parse_import(b"time,message\n1d,stretch").is_none());
+    }
+
+    #[test]
+    fn exported_reminders_import_the_same() {
+        let now = utc(2024, 3, 1, 12, 0);
+        let config = test_config(&[]);
+        let recurring = Reminder {
+            recurrence_interval: Some("1w".to_string()),
+            ..reminder("standup")
+        };
+        let reminders = [reminder("stretch"), recurring];
+        let file = serenity::json::to_vec_pretty(&export_reminders(
+            &reminders,
+            Some(chrono_tz::Europe::Warsaw),
+        ))
+        .unwrap();
+
+        // Read back by someone without a timezone of their own.
+        let imported: Vec<_> = parse_import(&file)
+            .unwrap()
+            .iter()
+            .map(|row| validate_import_row(row, None, now, &config, "!", DEFAULT_LOCALE))
+            .collect();
+        assert_eq!(
+            imported,
+            vec![
+                Ok((utc(2024, 3, 6, 12, 0), "stretch".to_string(), None)),
+                Ok((
+                    utc(2024, 3, 6, 12, 0),
+                    "standup".to_string(),
+                    Some("1w".to_string())
+                )),
+            ]
+        );
+
+        let row = |timezone: &str, every: &str| ImportRow {
+            time: "1d".to_string(),
+            timezone: Some(timezone.to_string()),
+            every: Some(every.to_string()),
+            ..ImportRow::default()
+        };
+        let validate =
+            |row: &ImportRow| validate_import_row(row, None, now, &config, "!", DEFAULT_LOCALE);
+        assert_eq!(
+            validate(&row("Mars/Olympus", "1d")),
+            Err("There's no timezone called `Mars/Olympus`".to_string())
+        );
+        assert!(validate(&row("UTC", "fortnightly"))
+            .unwrap_err()
+            .starts_with("I couldn't understand the date"));
+        assert!(validate(&row("UTC", "30s")).is_err());
+    }
+
+    #[test]
+    fn import_rows_are_checked_like_commands() {
+        let now = utc(2024, 3, 6, 12, 0);
+        let row = |time: &str, message: &str| ImportRow {
+            time: time.to_string(),
+            message: message.to_string(),
+            ..ImportRow::default()
+        };
+        let config = test_config(&[("MAX_MESSAGE_LENGTH", "10")]);
+        let validate =
+            |row: &ImportRow| validate_import_row(row, None, now, &config, "!", DEFAULT_LOCALE);
+
+        assert_eq!(
+            validate(&row("1d", "stretch")),
+            Ok((utc(2024, 3, 7, 12, 0), "stretch".to_string(), None))
+        );
+        assert_eq!(
+            validate(&row("2h", "a very long message")),
+            Ok((utc(2024, 3, 6, 14, 0), "a very ...".to_string(), None))
+        );
+        assert_eq!(
+            validate(&row("2024-03-01 12:00", "stretch")),
+            Err(PAST_DATE_MESSAGE.to_string())
+        );
+        assert_eq!(validate(&row("10y", "stretch")), Err(horizon_message(5)));
+        assert!(validate(&row("someday", "stretch"))
+            .unwrap_err()
+            .starts_with("I couldn't understand the date"));
+    }
+
+    #[test]
+    fn reschedules_only_to_times_ahead() {
+        let now = utc(2024, 3, 6, 12, 0);
+        let config = test_config(&[]);
+        let validate =
+            |date: &str| validate_trigger_time(date, None, now, &config, "!", DEFAULT_LOCALE);
+
+        assert_eq!(validate("3h"), Ok(utc(2024, 3, 6, 15, 0)));
+        assert_eq!(
+            validate("2024-03-01 12:00"),
+            Err(PAST_DATE_MESSAGE.to_string())
+        );
+        assert_eq!(validate("10y"), Err(horizon_message(5)));
+        assert!(validate("someday")
+            .unwrap_err()
+            .starts_with("I couldn't understand the date"));
+        assert_eq!(
+            rescheduled_message(2, utc(2024, 3, 6, 15, 0), None, now),
+            "Reminder 2 moved to 2024-03-06 15:00 UTC (in about 3 hours)"
+        );
+    }
+
+    #[test]
+    fn stores_dates_in_utc_whatever_the_users_timezone() {
+        let now = utc(2024, 5, 1, 12, 0);
+        let config = test_config(&[]);
+        // New York is on EDT, UTC-4, in June.
+        let new_york = Some(chrono_tz::America::New_York);
+        assert_eq!(
+            validate_trigger_time(
+                "2024-06-01-12-00",
+                new_york,
+                now,
+                &config,
+                "!",
+                DEFAULT_LOCALE
+            ),
+            Ok(utc(2024, 6, 1, 16, 0))
+        );
+    }
+
+    #[test]
+    fn imported_reminders_link_back_to_the_import() {
+        let message_id = imported_message_id(MessageId::new(2), 3);
+        assert_eq!(message_id, "2/3");
+        assert_eq!(reference_message_id(&message_id), Some(MessageId::new(2)));
+        assert_eq!(reference_message_id("2"), Some(MessageId::new(2)));
+        assert_eq!(reference_message_id("slash"), None);
+    }
+
+    #[test]
+    fn summarises_imports() {
+        let summary = ImportSummary {
+            imported: 2,
+            failed: vec![(3, PAST_DATE_MESSAGE.to_string())],
+            skipped: 0,
+        };
+        assert_eq!(
+            import_summary_message(&summary),
+            "Imported 2 of 3 reminders\n- row 3: That time is in the past"
+        );
+        let summary = ImportSummary {
+            imported: 100,
+            failed: Vec::new(),
+            skipped: 5,
+        };
+        assert_eq!(
+            import_summary_message(&summary),
+            "Imported 100 of 105 reminders, only the first 100 rows are read"
+        );
+    }
+
+    #[test]
+    fn test_reminders_fire_a_minute_later() {
+        assert!(is_test_command("!remindme test", "!"));
+        assert!(is_test_command(" !RemindMe TEST ", "!"));
+        assert!(!is_test_command("!remindme 1d test", "!"));
+        assert!(!is_test_command("!remindme test the oven", "!"));
+
+        let now = utc(2024, 3, 6, 12, 0);
+        let reminder = test_reminder("1".to_string(), "3".to_string(), "2".to_string(), now);
+        assert_eq!(reminder.user_id, "1");
+        assert_eq!(reminder.target_user_id, "1");
+        assert_eq!(reminder.channel_id, "3");
+        assert_eq!(reminder.message_id, "2");
+        assert_eq!(reminder.message_content, "This is a test reminder");
+        assert_eq!(reminder.trigger_time, utc(2024, 3, 6, 12, 1));
+        assert_eq!(reminder.status, STATUS_PENDING);
+        assert_eq!(reminder.recurrence_interval, None);
+    }
+
+    #[test]
+    fn reports_how_many_reminders_were_cancelled() {
+        assert_eq!(
+            cancelled_all_message(0),
+            "You have no pending reminders to cancel"
+        );
+        assert_eq!(
+            cancelled_all_message(1),
+            "Cancelled your 1 pending reminder"
+        );
+        assert_eq!(
+            cancelled_all_message(3),
+            "Cancelled all 3 of your pending reminders"
+        );
+    }
+
+    #[test]
+    fn shows_times_in_the_users_timezone() {
+        let noon = utc(2024, 6, 1, 12, 0);
+        let warsaw = Some(chrono_tz::Europe::Warsaw);
+        assert_eq!(format_trigger_time(noon, warsaw), "2024-06-01 14:00 CEST");
+
+        let mut at_noon = reminder("stretch");
+        at_noon.trigger_time = noon;
+        assert!(
+            format_reminder_list(&[at_noon], None, warsaw, 0, DEFAULT_LOCALE)
+                .contains("1. 2024-06-01 14:00 CEST - ")
+        );
+    }
+
+    #[test]
+    fn invalid_dates_are_quoted_back_safely() {
+        let message = invalid_date_message("tmrw`@everyone", "!", DEFAULT_LOCALE);
+        assert!(message.starts_with("I couldn't understand the date `tmrw'@\u{200b}everyone`."));
+        assert!(message.contains("`YYYY-MM-DD HH:MM`"));
+        assert!(invalid_date_message("someday", "?", DEFAULT_LOCALE)
+            .ends_with("for example `?remindme 1d water the plants`"));
+    }
+
+    #[test]
+    fn confirms_with_a_reaction_or_a_reply() {
+        let saved: Result<bool, ()> = Ok(true);
+        assert_eq!(
+            confirmation(&saved, true, "Reminder set".to_string()),
+            Confirmation {
+                reaction: Some('✅'),
+                reply: None,
+            }
+        );
+        assert_eq!(
+            confirmation(&saved, false, "Reminder set".to_string()),
+            Confirmation {
+                reaction: None,
+                reply: Some("Reminder set".to_string()),
+            }
+        );
+
+        // Failures are always explained, with a ❌ when reacting.
+        let failed: Result<bool, ()> = Err(());
+        assert_eq!(
+            confirmation(&failed, true, "Reminder set".to_string()),
+            Confirmation {
+                reaction: Some('❌'),
+                reply: Some(INSERT_FAILED_MESSAGE.to_string()),
+            }
+        );
+        assert_eq!(
+            confirmation(&failed, false, "Reminder set".to_string()),
+            Confirmation {
+                reaction: None,
+                reply: Some(INSERT_FAILED_MESSAGE.to_string()),
+            }
+        );
+
+        // A redelivered message doesn't set the reminder again.
+        let duplicate: Result<bool, ()> = Ok(false);
+        assert_eq!(
+            confirmation(&duplicate, true, "Reminder set".to_string()),
+            Confirmation {
+                reaction: None,
+                reply: Some(ALREADY_SET_MESSAGE.to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn failed_saves_are_reported() {
+        assert_eq!(
+            not_saved_message::<()>(&Err(())),
+            Some(INSERT_FAILED_MESSAGE)
+        );
+        assert_eq!(
+            not_saved_message::<()>(&Ok(false)),
+            Some(ALREADY_SET_MESSAGE)
+        );
+        assert_eq!(not_saved_message::<()>(&Ok(true)), None);
+    }
+
+    #[test]
+    fn cooldown_blocks_reminders_set_too_quickly() {
+        let cooldown = Duration::from_secs(2);
+        let last = Instant::now();
+        assert!(!on_cooldown(None, last, cooldown));
+        assert!(on_cooldown(Some(last), last, cooldown));
+        assert!(on_cooldown(
+            Some(last),
+            last + Duration::from_millis(1999),
+            cooldown
+        ));
+        assert!(!on_cooldown(Some(last), last + cooldown, cooldown));
+        // No cooldown configured never blocks.
+        assert!(!on_cooldown(Some(last), last, Duration::ZERO));
+    }
+
+    #[test]
+    fn reminder_limit_is_reached_at_exactly_the_limit() {
+        assert!(!reached_reminder_limit(0, 50));
+        assert!(!reached_reminder_limit(49, 50));
+        assert!(reached_reminder_limit(50, 50));
+        assert!(reached_reminder_limit(51, 50));
+        assert!(reached_reminder_limit(1, 1));
+    }
+
+    #[test]
+    fn rejects_reminders_beyond_the_horizon() {
+        let now = utc(2024, 3, 6, 12, 0);
+        let five_years = utc(2029, 3, 6, 12, 0);
+
+        assert!(!beyond_horizon(five_years, now, 5));
+        assert!(beyond_horizon(
+            five_years + chrono::Duration::seconds(1),
+            now,
+            5
+        ));
+        // Parses fine, but is far past any horizon.
+        let far = add_duration(now, "99999y").unwrap();
+        assert!(beyond_horizon(far, now, 5));
+        assert!(beyond_horizon(far, now, u32::MAX));
+        assert_eq!(
+            horizon_message(5),
+            "That's too far in the future, reminders can be at most 5 years ahead"
+        );
+    }
+
+    #[test]
+    fn lists_how_often_reminders_repeat() {
+        let mut daily = reminder("take vitamins");
+        daily.recurrence_interval = Some("1d".to_string());
+        assert!(
+            format_reminder_list(&[daily], None, None, 0, DEFAULT_LOCALE)
+                .contains("1. 2024-03-06 12:00 UTC (every 1d) - ")
+        );
+
+        let mut hourly = reminder("drink water");
+        hourly.recurrence_interval = Some("2h".to_string());
+        hourly.remaining_occurrences = Some(3);
+        assert!(
+            format_reminder_list(&[hourly], None, None, 0, DEFAULT_LOCALE)
+                .contains("1. 2024-03-06 12:00 UTC (every 2h, 3 left) - ")
+        );
+
+        let mut weekdays = reminder("standup");
+        weekdays.recurrence_interval = Some(r#"cron "0 9 * * 1-5""#.to_string());
+        assert!(
+            format_reminder_list(&[weekdays], None, None, 0, DEFAULT_LOCALE)
+                .contains("1. 2024-03-06 12:00 UTC (cron 0 9 * * 1-5) - ")
+        );
+    }
+
+    #[test]
+    fn tells_connection_errors_from_query_errors() {
+        assert!(is_connection_error(&sqlx::Error::PoolTimedOut));
+        assert!(is_connection_error(&sqlx::Error::PoolClosed));
+        assert!(is_connection_error(&sqlx::Error::Io(std::io::Error::from(
+            std::io::ErrorKind::ConnectionRefused
+        ))));
+        assert!(!is_connection_error(&sqlx::Error::RowNotFound));
+        assert!(!is_connection_error(&sqlx::Error::ColumnNotFound(
+            "trigger_time".to_string()
+        )));
+    }
+
+    #[test]
+    fn database_backoff_doubles_up_to_a_limit() {
+        assert_eq!(database_backoff(1), Duration::from_secs(2));
+        assert_eq!(database_backoff(2), Duration::from_secs(4));
+        assert_eq!(database_backoff(9), MAX_DATABASE_BACKOFF);
+        assert_eq!(database_backoff(u32::MAX), MAX_DATABASE_BACKOFF);
+    }
+
+    #[test]
+    fn mentioned_channels_override_the_current_one() {
+        let current = ChannelId::new(3);
+        assert_eq!(reminder_channel_id(None, current), Some(current));
+        assert_eq!(
+            reminder_channel_id(Some("456"), current),
+            Some(ChannelId::new(456))
+        );
+        assert_eq!(reminder_channel_id(Some("0"), current), None);
+        assert_eq!(
+            reminder_channel_id(Some("99999999999999999999"), current),
+            None
+        );
+    }
+
+    #[test]
+    fn cancels_by_text_only_when_one_reminder_matches() {
+        assert!(matches!(text_matches(Vec::new()), TextMatches::Nothing));
+        assert!(matches!(
+            text_matches(vec![reminder("water the plants")]),
+            TextMatches::One(reminder) if reminder.message_content == "water the plants"
+        ));
+        assert!(matches!(
+            text_matches(vec![reminder("water the plants"), reminder("water the lawn")]),
+            TextMatches::Several(reminders) if reminders.len() == 2
+        ));
+    }
+
+    #[test]
+    fn explains_text_matches_that_cant_be_cancelled() {
+        assert_eq!(
+            no_text_match_message("dentist"),
+            "None of your pending reminders mention \"dentist\""
+        );
+        assert!(!no_text_match_message("@everyone").contains("@everyone"));
+
+        let message = several_text_matches_message(
+            &[reminder("water the plants"), reminder("water the lawn")],
+            None,
+            "!",
+        );
+        assert_eq!(
+            message,
+            "2 reminders match, use a longer part of the message or `!cancel <number from !reminders>`:\n- 2024-03-06 12:00 UTC - water the plants\n- 2024-03-06 12:00 UTC - water the lawn\n"
+        );
+    }
+
+    #[test]
+    fn sending_needs_to_see_the_channel_too() {
+        assert!(can_send_messages(
+            Permissions::VIEW_CHANNEL | Permissions::SEND_MESSAGES
+        ));
+        assert!(can_send_messages(Permissions::from_bits_truncate(
+            (1 << 10) | (1 << 11) | (1 << 6)
+        )));
+        assert!(!can_send_messages(Permissions::SEND_MESSAGES));
+        assert!(!can_send_messages(Permissions::VIEW_CHANNEL));
+        assert!(!can_send_messages(Permissions::empty()));
+    }
+
+    #[test]
+    fn formats_reminder_stats() {
+        let stats = ReminderStats {
+            pending: 12,
+            oldest: Some(utc(2024, 3, 6, 11, 0)),
+            soonest: Some(utc(2024, 3, 6, 12, 30)),
+            by_source: vec![("text".to_string(), 10), ("slash".to_string(), 2)],
+        };
+        assert_eq!(
+            format_reminder_stats(&stats),
+            "Pending reminders: 12\nOldest: 2024-03-06 11:00 UTC\nSoonest: 2024-03-06 12:30 UTC\nBy source: text 10, slash 2"
+        );
+
+        let empty = ReminderStats {
+            pending: 0,
+            oldest: None,
+            soonest: None,
+            by_source: Vec::new(),
+        };
+        assert_eq!(
+            format_reminder_stats(&empty),
+            "Pending reminders: 0\nOldest: none\nSoonest: none\nBy source: none"
+        );
+    }
+
+    #[test]
+    fn bounded_repeats_count_down_and_reschedule() {
+        let mut hourly = reminder("drink water");
+        hourly.recurrence_interval = Some("2h".to_string());
+        hourly.remaining_occurrences = Some(5);
+        assert_eq!(
+            after_firing(&hourly),
+            AfterFiring::Reschedule {
+                interval: "2h",
+                remaining_occurrences: Some(4),
+            }
+        );
+
+        hourly.remaining_occurrences = None;
+        assert_eq!(
+            after_firing(&hourly),
+            AfterFiring::Reschedule {
+                interval: "2h",
+                remaining_occurrences: None,
+            }
+        );
+    }
+
+    #[test]
+    fn bounded_repeats_are_deleted_after_the_last_one() {
+        let mut hourly = reminder("drink water");
+        hourly.recurrence_interval = Some("2h".to_string());
+        hourly.remaining_occurrences = Some(1);
+        assert_eq!(after_firing(&hourly), AfterFiring::Delete);
+
+        assert_eq!(after_firing(&reminder("once")), AfterFiring::Delete);
+    }
+
+    #[test]
+    fn explains_date_errors() {
+        assert_eq!(
+            date_error_message(&DateParseError::OutOfRange, "25:00", "!", DEFAULT_LOCALE),
+            "There's no such date or time as `25:00`"
+        );
+        assert_eq!(
+            date_error_message(
+                &DateParseError::InPast,
+                "2024-03-01 12:00",
+                "!",
+                DEFAULT_LOCALE
+            ),
+            PAST_DATE_MESSAGE
+        );
     }
 }
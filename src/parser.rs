@@ -0,0 +1,1888 @@
+//! Reading commands, dates and durations out of message text.
+
+use chrono::{
+    DateTime, Datelike, Days, Local, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc,
+    Weekday,
+};
+use chrono_tz::Tz;
+use cron::Schedule;
+use regex::Regex;
+
+/// The lowercased first word without the prefix, `None` when it isn't a command.
+pub fn command_name(message: &str, prefix: &str) -> Option<String> {
+    message
+        .split_whitespace()
+        .next()?
+        .strip_prefix(prefix)
+        .map(str::to_lowercase)
+}
+
+pub fn is_list_alias(message: &str, prefix: &str) -> bool {
+    let regex = Regex::new(&format!(r"(?i)^{}remindme\s+list$", regex::escape(prefix))).unwrap();
+
+    regex.is_match(message.trim())
+}
+
+pub fn is_cancel_all(message: &str, prefix: &str) -> bool {
+    let regex = Regex::new(&format!(
+        r"(?i)^{}remindme\s+cancel\s+all$",
+        regex::escape(prefix)
+    ))
+    .unwrap();
+
+    regex.is_match(message.trim())
+}
+
+pub fn is_test_command(message: &str, prefix: &str) -> bool {
+    let regex = Regex::new(&format!(r"(?i)^{}remindme\s+test$", regex::escape(prefix))).unwrap();
+
+    regex.is_match(message.trim())
+}
+
+pub fn is_import_command(message: &str, prefix: &str) -> bool {
+    let regex = Regex::new(&format!(
+        r"(?i)^{}remindme\s+import$",
+        regex::escape(prefix)
+    ))
+    .unwrap();
+
+    regex.is_match(message.trim())
+}
+
+pub fn is_export_command(message: &str, prefix: &str) -> bool {
+    let regex = Regex::new(&format!(
+        r"(?i)^{}remindme\s+export$",
+        regex::escape(prefix)
+    ))
+    .unwrap();
+
+    regex.is_match(message.trim())
+}
+
+/// A `remindme after <n> +<offset> [text]` command, set relative to the
+/// reminder numbered `index` in `!reminders`.
+#[derive(Debug, PartialEq)]
+pub struct AnchoredCommand {
+    pub index: usize,
+    pub offset: String,
+    pub text: Option<String>,
+}
+
+pub fn parse_anchored_command(message: &str, prefix: &str) -> Option<AnchoredCommand> {
+    let regex = Regex::new(&format!(
+        r"(?s)^(?i:{}remindme)\s+(?i:after)\s+(\d+)\s+\+(\S+)(?:\s+(.+))?$",
+        regex::escape(prefix)
+    ))
+    .unwrap();
+
+    regex.captures(message.trim()).and_then(|caps| {
+        Some(AnchoredCommand {
+            index: caps[1].parse().ok()?,
+            offset: caps[2].to_string(),
+            text: caps.get(3).map(|text| text.as_str().to_string()),
+        })
+    })
+}
+
+/// The anchor's trigger time moved on by `offset`, a duration like `30m`.
+pub fn anchored_trigger_time(anchor: DateTime<Utc>, offset: &str) -> Option<DateTime<Utc>> {
+    add_duration(anchor, offset)
+}
+
+/// The parts of a `remindme` command, `date_str` is empty when none was given.
+pub struct ReminderCommand {
+    pub target_user_id: Option<String>,
+    /// A role mentioned to ping instead of a user.
+    pub target_role_id: Option<String>,
+    /// A channel mentioned to fire the reminder in instead of this one.
+    pub channel_id: Option<String>,
+    pub date_str: String,
+    pub text: Option<String>,
+    pub recurring: bool,
+    /// How many times a `repeat <n> times every` reminder goes out.
+    pub repeat_count: Option<i32>,
+    pub deliver_dm: bool,
+    /// Set with `!high` to fire before reminders due at the same time.
+    pub high_priority: bool,
+    /// A lowercased `#tag` from the end of the command.
+    pub tag: Option<String>,
+}
+
+/// The command without a trailing `#tag`, and the tag lowercased. Channel
+/// mentions look like `<#123>`, so they're never taken for one.
+pub fn split_tag(message: &str) -> (&str, Option<String>) {
+    let regex = Regex::new(r"(?s)^(.*\S)\s+#([\w-]+)\s*$").unwrap();
+
+    match regex.captures(message) {
+        Some(caps) => (
+            caps.get(1).map_or(message, |m| m.as_str()),
+            Some(caps[2].to_lowercase()),
+        ),
+        None => (message, None),
+    }
+}
+
+/// The tag in `reminders <tag>`, with or without its `#`.
+pub fn parse_reminders_tag(message: &str, prefix: &str) -> Option<String> {
+    let regex = Regex::new(&format!(
+        r"^(?i:{}reminders)\s+#?([\w-]+)$",
+        regex::escape(prefix)
+    ))
+    .unwrap();
+
+    regex
+        .captures(message.trim())
+        .map(|caps| caps[1].to_lowercase())
+}
+
+pub fn parse_reminder_command(message: &str, prefix: &str) -> Option<ReminderCommand> {
+    let (message, tag) = split_tag(message);
+    // Built by concatenation, the pattern's own braces would need escaping in `format!`.
+    let regex =
+        Regex::new(&(r"(?i:".to_string() + &regex::escape(prefix) + r"remindme)(?:\s+((?i:!high)\s+)?(?:<@!?(\d+)>\s+|<@&(\d+)>\s+)?(?:<#(\d+)>\s+)?((?i:dm)\s+)?(?:(?i:repeat\s+([1-9]\d{0,3})\s+times?\s+)?((?i:every)\s+))?((?i:cron)\s+[\x22“][^\x22”]+[\x22”]|\d{4}-\d{2}-\d{2}\s+\d{1,2}:\d{2}(?::\d{2})?|(?i:in\s+(?:\d+|an?)\s+[a-z]+|(?:today|tomorrow|(?:next\s+)?(?:mon|tues|wednes|thurs|fri|satur|sun)day)(?:\s+(?:at\s+)?\d{1,2}(?::\d{2}(?:am|pm)?|am|pm))?)\b|\S+)(?:\s+((?s:.+)))?|\s*$)"))
+            .unwrap();
+
+    let caps = regex.captures(message)?;
+    // `in 2h`, `on 2024-06-01` and `at 9am` read the same as without the
+    // preposition. Phrases like `in 2 hours` are matched whole, so never land here.
+    let preposition = caps
+        .get(8)
+        .filter(|date| ["in", "on", "at"].contains(&date.as_str().to_lowercase().as_str()));
+    match preposition {
+        Some(preposition) => {
+            let without = format!(
+                "{}{}",
+                &message[..preposition.start()],
+                message[preposition.end()..].trim_start()
+            );
+            regex
+                .captures(&without)
+                .map(|caps| reminder_command(&caps, tag))
+        }
+        None => Some(reminder_command(&caps, tag)),
+    }
+}
+
+fn reminder_command(caps: &regex::Captures, tag: Option<String>) -> ReminderCommand {
+    ReminderCommand {
+        high_priority: caps.get(1).is_some(),
+        target_user_id: caps.get(2).map(|m| m.as_str().to_string()),
+        target_role_id: caps.get(3).map(|m| m.as_str().to_string()),
+        channel_id: caps.get(4).map(|m| m.as_str().to_string()),
+        deliver_dm: caps.get(5).is_some(),
+        repeat_count: caps.get(6).and_then(|m| m.as_str().parse().ok()),
+        recurring: caps.get(7).is_some()
+            || caps
+                .get(8)
+                .is_some_and(|date| cron_expression(date.as_str()).is_some()),
+        date_str: caps.get(8).map_or("", |m| m.as_str()).to_string(),
+        text: caps.get(9).map(|m| m.as_str().to_string()),
+        tag,
+    }
+}
+
+fn parse_snooze_command(message: &str, prefix: &str) -> Option<String> {
+    let regex = Regex::new(&format!(r"^(?i:{}snooze)\s+(\S+)$", regex::escape(prefix))).unwrap();
+
+    regex
+        .captures(message.trim())
+        .map(|caps| caps[1].to_string())
+}
+
+/// When `!snooze <duration>` fires the reminder again, `None` when the
+/// duration can't be read.
+pub fn snooze_until(message: &str, prefix: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    parse_snooze_command(message, prefix).and_then(|duration| add_duration(now, &duration))
+}
+
+pub fn parse_edit_command(message: &str, prefix: &str) -> Option<(usize, String)> {
+    let regex = Regex::new(&format!(
+        r"(?s)^(?i:{}edit)\s+(\d+)\s+(.+)$",
+        regex::escape(prefix)
+    ))
+    .unwrap();
+
+    regex.captures(message.trim()).and_then(|caps| {
+        let index = caps.get(1)?.as_str().parse::<usize>().ok()?;
+        Some((index, caps.get(2)?.as_str().to_string()))
+    })
+}
+
+pub fn parse_reschedule_command(message: &str, prefix: &str) -> Option<(usize, String)> {
+    let regex = Regex::new(&format!(
+        r"^(?i:{}reschedule)\s+(\d+)\s+(.+)$",
+        regex::escape(prefix)
+    ))
+    .unwrap();
+
+    regex.captures(message.trim()).and_then(|caps| {
+        let index = caps.get(1)?.as_str().parse::<usize>().ok()?;
+        Some((index, caps.get(2)?.as_str().to_string()))
+    })
+}
+
+pub fn parse_cancel_command(message: &str, prefix: &str) -> Option<usize> {
+    let regex = Regex::new(&format!(r"^(?i:{}cancel)\s+(\d+)$", regex::escape(prefix))).unwrap();
+
+    regex
+        .captures(message.trim())
+        .and_then(|caps| caps.get(1)?.as_str().parse::<usize>().ok())
+}
+
+/// The fragment in `cancel text <fragment>`.
+pub fn parse_cancel_text_command(message: &str, prefix: &str) -> Option<String> {
+    let regex = Regex::new(&format!(
+        r"(?s)^(?i:{}cancel)\s+(?i:text)\s+(.+)$",
+        regex::escape(prefix)
+    ))
+    .unwrap();
+
+    regex
+        .captures(message.trim())
+        .map(|caps| caps[1].to_string())
+}
+
+pub fn parse_timezone_command(message: &str, prefix: &str) -> Option<Tz> {
+    let regex = Regex::new(&format!(
+        r"^(?i:{}timezone)\s+(\S+)$",
+        regex::escape(prefix)
+    ))
+    .unwrap();
+
+    regex
+        .captures(message.trim())
+        .and_then(|caps| caps.get(1)?.as_str().parse::<Tz>().ok())
+}
+
+pub fn parse_locale_command(message: &str, prefix: &str) -> Option<String> {
+    let regex = Regex::new(&format!(r"^(?i:{}locale)\s+(\S+)$", regex::escape(prefix))).unwrap();
+
+    regex
+        .captures(message.trim())
+        .map(|caps| caps[1].to_string())
+}
+
+/// The date in `whenis <date>`, which is only read back, not set.
+pub fn parse_whenis_command(message: &str, prefix: &str) -> Option<String> {
+    let regex = Regex::new(&format!(
+        r"(?s)^(?i:{}whenis)\s+(.+)$",
+        regex::escape(prefix)
+    ))
+    .unwrap();
+
+    regex
+        .captures(message.trim())
+        .map(|caps| caps[1].to_string())
+}
+
+/// `Some(true)` for `digest on`, `Some(false)` for `digest off`.
+pub fn parse_digest_command(message: &str, prefix: &str) -> Option<bool> {
+    let regex = Regex::new(&format!(
+        r"^(?i:{}digest)\s+(?i:(on)|off)$",
+        regex::escape(prefix)
+    ))
+    .unwrap();
+
+    regex
+        .captures(message.trim())
+        .map(|caps| caps.get(1).is_some())
+}
+
+pub fn parse_upcoming_command(message: &str, prefix: &str) -> Option<u32> {
+    let regex = Regex::new(&format!(
+        r"^(?i:{}upcoming)\s+(\d+)$",
+        regex::escape(prefix)
+    ))
+    .unwrap();
+
+    regex
+        .captures(message.trim())
+        .and_then(|caps| caps[1].parse().ok())
+}
+
+/// What `settings` does, `None` going back to the global setting.
+#[derive(Debug, PartialEq)]
+pub enum SettingsCommand {
+    Show,
+    Prefix(Option<String>),
+    ReminderLimit(Option<i64>),
+}
+
+/// Server prefixes are typed before every command, so they're kept short.
+pub const MAX_PREFIX_LENGTH: usize = 5;
+
+pub fn parse_settings_command(message: &str, prefix: &str) -> Option<SettingsCommand> {
+    let regex = Regex::new(&format!(
+        r"^(?i:{}settings)(?:\s+(\w+)\s+(\S+))?$",
+        regex::escape(prefix)
+    ))
+    .unwrap();
+
+    let caps = regex.captures(message.trim())?;
+    let (Some(setting), Some(value)) = (caps.get(1), caps.get(2)) else {
+        return Some(SettingsCommand::Show);
+    };
+    let value = value.as_str();
+    let default = value.eq_ignore_ascii_case("default");
+    match setting.as_str().to_lowercase().as_str() {
+        "prefix" if default => Some(SettingsCommand::Prefix(None)),
+        "prefix" if value.chars().count() <= MAX_PREFIX_LENGTH => {
+            Some(SettingsCommand::Prefix(Some(value.to_string())))
+        }
+        "limit" if default => Some(SettingsCommand::ReminderLimit(None)),
+        "limit" => value
+            .parse()
+            .ok()
+            .filter(|limit| *limit > 0)
+            .map(|limit| SettingsCommand::ReminderLimit(Some(limit))),
+        _ => None,
+    }
+}
+
+/// Why a date couldn't be turned into a trigger time.
+#[derive(Debug, PartialEq)]
+pub enum DateParseError {
+    /// Not any date, time or duration we understand.
+    UnknownFormat,
+    /// Shaped like a date or time that doesn't exist, like `2024-02-30 12:00`,
+    /// `25:00` or a time skipped by a DST change.
+    OutOfRange,
+    InPast,
+    /// A duration too large to add.
+    Overflow,
+}
+
+pub fn parse_date_str(
+    date_str: &str,
+    timezone: Option<Tz>,
+    now: DateTime<Utc>,
+    default_time: NaiveTime,
+    holidays: &[NaiveDate],
+) -> Result<DateTime<Utc>, DateParseError> {
+    if let Some(seconds) = date_str.strip_prefix('@') {
+        let trigger_time = parse_timestamp(seconds)?;
+        if trigger_time < now {
+            return Err(DateParseError::InPast);
+        }
+        return Ok(trigger_time);
+    }
+
+    if let Some(days) = parse_business_days(date_str) {
+        return match timezone {
+            Some(tz) => after_business_days(&tz, now, days, holidays),
+            None => after_business_days(&Local, now, days, holidays),
+        };
+    }
+
+    let natural = match timezone {
+        Some(tz) => parse_natural_date(&tz, now, date_str),
+        None => parse_natural_date(&Local, now, date_str),
+    };
+    if let Some(natural) = natural {
+        // `today 9am` names a fixed time that may already have gone by.
+        if natural < now {
+            return Err(DateParseError::InPast);
+        }
+        return Ok(natural);
+    }
+
+    if let Some(wall_clock) = parse_absolute_datetime(date_str, default_time) {
+        let trigger_time = match timezone {
+            Some(tz) => tz
+                .from_local_datetime(&wall_clock)
+                .earliest()
+                .map(|time| time.to_utc()),
+            None => Local
+                .from_local_datetime(&wall_clock)
+                .earliest()
+                .map(|time| time.to_utc()),
+        }
+        .ok_or(DateParseError::OutOfRange)?;
+        // Durations and times of day always resolve after `now`, so only
+        // absolute and natural dates can be in the past.
+        if trigger_time < now {
+            return Err(DateParseError::InPast);
+        }
+        Ok(trigger_time)
+    } else if let Some(time) = parse_time_of_day(date_str) {
+        match timezone {
+            Some(tz) => next_time_of_day(&tz, now, time),
+            None => next_time_of_day(&Local, now, time),
+        }
+        .ok_or(DateParseError::OutOfRange)
+    } else if looks_like_date(date_str) {
+        Err(DateParseError::OutOfRange)
+    } else {
+        parse_duration(date_str)?;
+        add_duration(now, date_str).ok_or(DateParseError::Overflow)
+    }
+}
+
+/// Seconds since the Unix epoch, as in `@1717251000`.
+fn parse_timestamp(seconds: &str) -> Result<DateTime<Utc>, DateParseError> {
+    let timestamp_regex = Regex::new(r"^-?\d+$").unwrap();
+
+    if !timestamp_regex.is_match(seconds) {
+        return Err(DateParseError::UnknownFormat);
+    }
+    let seconds = seconds
+        .parse::<i64>()
+        .map_err(|_| DateParseError::Overflow)?;
+    if seconds < 0 {
+        return Err(DateParseError::OutOfRange);
+    }
+    DateTime::from_timestamp(seconds, 0).ok_or(DateParseError::Overflow)
+}
+
+/// The number of days in `3bd`.
+fn parse_business_days(date_str: &str) -> Option<u32> {
+    let regex = Regex::new(r"^(?i)([1-9]\d*)bd$").unwrap();
+
+    regex
+        .captures(date_str)
+        .and_then(|caps| caps[1].parse().ok())
+}
+
+/// `days` business days after today in `timezone`, at the current time of day.
+fn after_business_days<T: TimeZone>(
+    timezone: &T,
+    now: DateTime<Utc>,
+    days: u32,
+    holidays: &[NaiveDate],
+) -> Result<DateTime<Utc>, DateParseError> {
+    let local = now.with_timezone(timezone).naive_local();
+    let date = add_business_days(local.date(), days, holidays).ok_or(DateParseError::Overflow)?;
+    timezone
+        .from_local_datetime(&date.and_time(local.time()))
+        .earliest()
+        .map(|time| time.to_utc())
+        .ok_or(DateParseError::OutOfRange)
+}
+
+/// Saturdays, Sundays and `holidays` are skipped.
+fn add_business_days(date: NaiveDate, days: u32, holidays: &[NaiveDate]) -> Option<NaiveDate> {
+    let start = date;
+    let mut date = add_weekdays(date, days)?;
+    // Each holiday on a weekday passed on the way pushes the result one
+    // weekday later, which can pass more holidays.
+    let mut skipped = 0;
+    loop {
+        let holidays_passed = holidays
+            .iter()
+            .filter(|holiday| **holiday > start && **holiday <= date && !is_weekend(**holiday))
+            .count();
+        if holidays_passed == skipped {
+            return Some(date);
+        }
+        date = add_weekdays(date, u32::try_from(holidays_passed - skipped).ok()?)?;
+        skipped = holidays_passed;
+    }
+}
+
+fn is_weekend(date: NaiveDate) -> bool {
+    matches!(date.weekday(), Weekday::Sat | Weekday::Sun)
+}
+
+/// Counting from a weekend day is the same as counting from the Friday
+/// before it.
+fn add_weekdays(date: NaiveDate, days: u32) -> Option<NaiveDate> {
+    let mut date = match date.weekday() {
+        Weekday::Sat => date.pred_opt()?,
+        Weekday::Sun => date.checked_sub_days(Days::new(2))?,
+        _ => date,
+    };
+    // Every five weekdays are a whole week.
+    date = date.checked_add_days(Days::new(u64::from(days / 5) * 7))?;
+    for _ in 0..days % 5 {
+        date = date.succ_opt()?;
+        while is_weekend(date) {
+            date = date.succ_opt()?;
+        }
+    }
+    Some(date)
+}
+
+/// Shaped like an absolute date or a time of day, whether or not it exists.
+fn looks_like_date(date_str: &str) -> bool {
+    let regex = Regex::new(
+        r"(?i)^(?:\d{4}-\d{2}-\d{2}(?:[ T-]\d{1,2}[:-]\d{2}(?::\d{2})?)?|\d{1,2}(?::\d{2})?(?:am|pm)|\d{1,2}:\d{2})$",
+    )
+    .unwrap();
+
+    regex.is_match(date_str)
+}
+
+/// Understands `in 2 hours`, `tomorrow`, `today 17:30` and `next friday 9am`.
+/// Without an explicit time, days keep the current time of day.
+fn parse_natural_date<T: TimeZone>(
+    timezone: &T,
+    now: DateTime<Utc>,
+    date_str: &str,
+) -> Option<DateTime<Utc>> {
+    let date_str = date_str.to_lowercase();
+    let words: Vec<&str> = date_str.split_whitespace().collect();
+
+    let (day, time_words) = match words.as_slice() {
+        ["in", amount, unit] => {
+            let amount = match *amount {
+                "a" | "an" => 1,
+                amount => amount.parse::<u32>().ok()?,
+            };
+            let unit = match unit.trim_end_matches('s') {
+                "sec" | "second" => "s",
+                "min" | "minute" => "min",
+                "hr" | "hour" => "h",
+                "day" => "d",
+                "week" => "w",
+                "month" => "mo",
+                "year" => "y",
+                _ => return None,
+            };
+            return add_duration(now, &format!("{}{}", amount, unit));
+        }
+        ["next", day, rest @ ..] if day.parse::<Weekday>().is_ok() => (*day, rest),
+        [day, rest @ ..] => (*day, rest),
+        [] => return None,
+    };
+
+    let local_now = now.with_timezone(timezone);
+    let today = local_now.date_naive();
+    let date = match day {
+        "today" => today,
+        "tomorrow" => today.succ_opt()?,
+        day => {
+            let weekday = day.parse::<Weekday>().ok()?;
+            let days_ahead =
+                (weekday.num_days_from_monday() + 7 - today.weekday().num_days_from_monday()) % 7;
+            // `monday` on a Monday means the following week, not today.
+            let days_ahead = if days_ahead == 0 { 7 } else { days_ahead };
+            today.checked_add_days(Days::new(days_ahead.into()))?
+        }
+    };
+
+    let time = match time_words {
+        [] if day == "today" => return None,
+        [] => local_now.time(),
+        [time] | ["at", time] => parse_time_of_day(time)?,
+        _ => return None,
+    };
+
+    Some(
+        timezone
+            .from_local_datetime(&date.and_time(time))
+            .earliest()?
+            .to_utc(),
+    )
+}
+
+/// A date on its own is at `default_time`.
+fn parse_absolute_datetime(date_str: &str, default_time: NaiveTime) -> Option<NaiveDateTime> {
+    const FORMATS: [&str; 4] = [
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%d %H:%M",
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+    ];
+    if let Some(wall_clock) = FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(date_str, format).ok())
+    {
+        return Some(wall_clock);
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(date_str, "%Y-%m-%d") {
+        return Some(date.and_time(default_time));
+    }
+
+    let datetime_regex = Regex::new(r"^(\d{4})-(\d{2})-(\d{2})-(\d{2})-(\d{2})$").unwrap();
+
+    let caps = datetime_regex.captures(date_str)?;
+    let year = caps.get(1)?.as_str().parse::<i32>().ok()?;
+    let month = caps.get(2)?.as_str().parse::<u32>().ok()?;
+    let day = caps.get(3)?.as_str().parse::<u32>().ok()?;
+    let hour = caps.get(4)?.as_str().parse::<u32>().ok()?;
+    let minute = caps.get(5)?.as_str().parse::<u32>().ok()?;
+
+    NaiveDateTime::parse_from_str(
+        &format!("{}-{}-{} {}:{}:00", year, month, day, hour, minute),
+        "%Y-%m-%d %H:%M:%S",
+    )
+    .ok()
+}
+
+fn parse_time_of_day(time_str: &str) -> Option<NaiveTime> {
+    let time_regex = Regex::new(r"^(\d{1,2})(?::(\d{2}))?(am|pm)?$").unwrap();
+
+    let time_str = time_str.to_lowercase();
+    let caps = time_regex.captures(&time_str)?;
+    let hour = caps.get(1)?.as_str().parse::<u32>().ok()?;
+    let minute = caps
+        .get(2)
+        .map_or(Some(0), |m| m.as_str().parse::<u32>().ok())?;
+
+    let hour = match caps.get(3).map(|m| m.as_str()) {
+        Some(_) if !(1..=12).contains(&hour) => return None,
+        Some("am") => hour % 12,
+        Some(_) => hour % 12 + 12,
+        // A bare number like `9` is too ambiguous, require `9:00` or `9am`.
+        None if caps.get(2).is_none() => return None,
+        None => hour,
+    };
+
+    NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+fn next_time_of_day<T: TimeZone>(
+    timezone: &T,
+    now: DateTime<Utc>,
+    time: NaiveTime,
+) -> Option<DateTime<Utc>> {
+    let today = now.with_timezone(timezone).date_naive();
+    let candidate = timezone
+        .from_local_datetime(&today.and_time(time))
+        .earliest()?
+        .to_utc();
+    if candidate > now {
+        return Some(candidate);
+    }
+
+    let tomorrow = today.succ_opt()?;
+    Some(
+        timezone
+            .from_local_datetime(&tomorrow.and_time(time))
+            .earliest()?
+            .to_utc(),
+    )
+}
+
+/// A weekday with a time, like `monday 9am` or `friday at 17:30`.
+fn parse_weekly_rule(rule: &str) -> Option<(Weekday, NaiveTime)> {
+    let rule = rule.to_lowercase();
+    let words: Vec<&str> = rule.split_whitespace().collect();
+    match words.as_slice() {
+        [day, time] | [day, "at", time] => Some((day.parse().ok()?, parse_time_of_day(time)?)),
+        _ => None,
+    }
+}
+
+/// The first `weekday` at `time` after `after`, a week later when the time
+/// doesn't exist that day because of a DST change.
+fn next_weekday_time<T: TimeZone>(
+    timezone: &T,
+    after: DateTime<Utc>,
+    weekday: Weekday,
+    time: NaiveTime,
+) -> Option<DateTime<Utc>> {
+    let today = after.with_timezone(timezone).date_naive();
+    (0..15)
+        .filter_map(|days| today.checked_add_days(Days::new(days)))
+        .filter(|date| date.weekday() == weekday)
+        .filter_map(|date| {
+            timezone
+                .from_local_datetime(&date.and_time(time))
+                .earliest()
+        })
+        .map(|time| time.to_utc())
+        .find(|time| *time > after)
+}
+
+/// `None` for amounts too large to add, instead of overflowing.
+pub fn add_duration(from: DateTime<Utc>, duration_str: &str) -> Option<DateTime<Utc>> {
+    let (months, duration) = parse_duration(duration_str).ok()?;
+    // Calendar-aware: Jan 31 + 1mo lands on the last day of February.
+    from.checked_add_months(Months::new(months))?
+        .checked_add_signed(duration)
+}
+
+/// The months in a duration like `1mo2w`, which vary in length, and the rest.
+fn parse_duration(duration_str: &str) -> Result<(u32, chrono::Duration), DateParseError> {
+    // `mo` and `min` are tried first, so `m` alone is always minutes.
+    let full_regex = Regex::new(r"^(?:\d+(?:mo|min|[smhdwy]))+$").unwrap();
+    let component_regex = Regex::new(r"(\d+)(mo|min|[smhdwy])").unwrap();
+
+    if !full_regex.is_match(duration_str) {
+        return Err(DateParseError::UnknownFormat);
+    }
+
+    let mut seen_units = Vec::new();
+    let mut months = 0u32;
+    let mut duration = chrono::Duration::zero();
+    for caps in component_regex.captures_iter(duration_str) {
+        let amount = caps[1]
+            .parse::<i64>()
+            .map_err(|_| DateParseError::Overflow)?;
+        let unit = match caps.get(2).map_or("", |m| m.as_str()) {
+            "min" => "m",
+            unit => unit,
+        };
+        if seen_units.contains(&unit) {
+            return Err(DateParseError::UnknownFormat);
+        }
+        seen_units.push(unit);
+
+        let component = match unit {
+            "s" => chrono::Duration::try_seconds(amount),
+            "m" => chrono::Duration::try_minutes(amount),
+            "h" => chrono::Duration::try_hours(amount),
+            "d" => chrono::Duration::try_days(amount),
+            "w" => chrono::Duration::try_weeks(amount),
+            "mo" => {
+                months = u32::try_from(amount).map_err(|_| DateParseError::Overflow)?;
+                continue;
+            }
+            "y" => amount.checked_mul(365).and_then(chrono::Duration::try_days),
+            _ => return Err(DateParseError::UnknownFormat),
+        };
+        duration = component
+            .and_then(|component| duration.checked_add(&component))
+            .ok_or(DateParseError::Overflow)?;
+    }
+    Ok((months, duration))
+}
+
+/// Durations using the bare `m`, which some read as months, for minutes.
+pub fn uses_legacy_minutes(duration_str: &str) -> bool {
+    let legacy_regex = Regex::new(r"\dm(?:\d|$)").unwrap();
+
+    parse_duration(duration_str).is_ok() && legacy_regex.is_match(duration_str)
+}
+
+/// The expression in a `cron "0 9 * * 1-5"` rule.
+pub fn cron_expression(rule: &str) -> Option<&str> {
+    let regex = Regex::new(r#"^(?i:cron)\s+["“]([^"”]+)["”]$"#).unwrap();
+
+    regex
+        .captures(rule.trim())
+        .and_then(|caps| caps.get(1))
+        .map(|m| m.as_str().trim())
+}
+
+/// A five field crontab expression: minute, hour, day of month, month and day
+/// of week. `None` when it doesn't parse or never fires.
+pub fn parse_cron(expression: &str) -> Option<Schedule> {
+    let fields: Vec<&str> = expression.split_whitespace().collect();
+    let [minute, hour, day, month, weekday] = fields.as_slice() else {
+        return None;
+    };
+    // The `cron` crate wants seconds first and counts weekdays from 1 for
+    // Sunday, crontab from 0, so numbered weekdays are passed by name.
+    let weekday = cron_weekdays(weekday)?;
+    let schedule: Schedule = format!("0 {} {} {} {} {}", minute, hour, day, month, weekday)
+        .parse()
+        .ok()?;
+    schedule.upcoming(Utc).next()?;
+    Some(schedule)
+}
+
+fn cron_weekdays(field: &str) -> Option<String> {
+    const DAYS: [&str; 8] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+    let name = |day: &str| match day.parse::<usize>() {
+        Ok(number) => DAYS.get(number).map(|name| name.to_string()),
+        Err(_) => Some(day.to_string()),
+    };
+    let parts = field
+        .split(',')
+        .map(|part| {
+            let (range, step) = match part.split_once('/') {
+                Some((range, step)) => (range, Some(step)),
+                None => (part, None),
+            };
+            let range = range.split('-').map(name).collect::<Option<Vec<_>>>()?;
+            Some(match step {
+                Some(step) => format!("{}/{}", range.join("-"), step),
+                None => range.join("-"),
+            })
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(parts.join(","))
+}
+
+fn next_cron_time<T: TimeZone>(
+    schedule: &Schedule,
+    timezone: &T,
+    after: DateTime<Utc>,
+) -> Option<DateTime<Utc>> {
+    schedule
+        .after(&after.with_timezone(timezone))
+        .next()
+        .map(|next| next.with_timezone(&Utc))
+}
+
+/// A repeating reminder can fire at most once a minute.
+const MIN_RECURRENCE_SECONDS: i64 = 60;
+
+/// The first occurrence after `now` of a reminder last due at `last`, skipping
+/// any missed while the bot was offline instead of firing them all. Intervals
+/// are durations, a weekday and time like `monday 9am` or a `cron "..."` rule,
+/// the last two read in `timezone`. `None` for anything else and for
+/// durations that repeat too often.
+pub fn next_recurrence_after(
+    interval: &str,
+    last: DateTime<Utc>,
+    now: DateTime<Utc>,
+    timezone: Option<Tz>,
+) -> Option<DateTime<Utc>> {
+    if let Some(expression) = cron_expression(interval) {
+        let schedule = parse_cron(expression)?;
+        let after = last.max(now);
+        return match timezone {
+            Some(tz) => next_cron_time(&schedule, &tz, after),
+            None => next_cron_time(&schedule, &Local, after),
+        };
+    }
+    if let Some((weekday, time)) = parse_weekly_rule(interval) {
+        let after = last.max(now);
+        return match timezone {
+            Some(tz) => next_weekday_time(&tz, after, weekday, time),
+            None => next_weekday_time(&Local, after, weekday, time),
+        };
+    }
+
+    let next = add_duration(last, interval)?;
+    if next - last < chrono::Duration::seconds(MIN_RECURRENCE_SECONDS) {
+        return None;
+    }
+    if next > now {
+        return Some(next);
+    }
+    match parse_duration(interval).ok()? {
+        // Every step is the same length, so the missed ones can be counted.
+        (0, step) => {
+            let step = step.num_seconds();
+            let missed = (now - next).num_seconds() / step + 1;
+            missed
+                .checked_mul(step)
+                .and_then(chrono::Duration::try_seconds)
+                .and_then(|skipped| next.checked_add_signed(skipped))
+        }
+        // Months differ in length, but each step is at least 28 days.
+        _ => {
+            let mut next = next;
+            while next <= now {
+                next = add_duration(next, interval)?;
+            }
+            Some(next)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, day, hour, minute, 0)
+            .unwrap()
+    }
+
+    fn nine_am() -> NaiveTime {
+        NaiveTime::from_hms_opt(9, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn snoozes_by_the_given_duration() {
+        let now = utc(2024, 3, 6, 12, 0);
+        assert_eq!(
+            snooze_until("!snooze 10m", "!", now),
+            Some(utc(2024, 3, 6, 12, 10))
+        );
+        assert_eq!(
+            snooze_until(" !snooze 1d ", "!", now),
+            Some(utc(2024, 3, 7, 12, 0))
+        );
+    }
+
+    #[test]
+    fn parses_anchored_reminders() {
+        assert_eq!(
+            parse_anchored_command("!remindme after 2 +30m take the pizza out", "!"),
+            Some(AnchoredCommand {
+                index: 2,
+                offset: "30m".to_string(),
+                text: Some("take the pizza out".to_string()),
+            })
+        );
+        assert_eq!(
+            parse_anchored_command(" !RemindMe AFTER 1 +1h ", "!"),
+            Some(AnchoredCommand {
+                index: 1,
+                offset: "1h".to_string(),
+                text: None,
+            })
+        );
+        assert_eq!(parse_anchored_command("!remindme after 1 30m", "!"), None);
+        assert_eq!(parse_anchored_command("!remindme after +30m", "!"), None);
+        assert_eq!(
+            parse_anchored_command("!remindme 1d after lunch", "!"),
+            None
+        );
+    }
+
+    #[test]
+    fn anchored_reminders_add_the_offset_to_the_anchor() {
+        let anchor = utc(2024, 3, 6, 15, 0);
+        assert_eq!(
+            anchored_trigger_time(anchor, "30m"),
+            Some(utc(2024, 3, 6, 15, 30))
+        );
+        assert_eq!(
+            anchored_trigger_time(anchor, "1d2h"),
+            Some(utc(2024, 3, 7, 17, 0))
+        );
+        assert_eq!(
+            anchored_trigger_time(utc(2024, 1, 31, 9, 0), "1mo"),
+            Some(utc(2024, 2, 29, 9, 0))
+        );
+        assert_eq!(anchored_trigger_time(anchor, "later"), None);
+        assert_eq!(anchored_trigger_time(anchor, "99999999999y"), None);
+    }
+
+    #[test]
+    fn snoozing_needs_a_duration() {
+        let now = utc(2024, 3, 6, 12, 0);
+        assert_eq!(snooze_until("!snooze", "!", now), None);
+        assert_eq!(snooze_until("!snooze later", "!", now), None);
+        assert_eq!(snooze_until("!snooze 1h please", "!", now), None);
+        assert_eq!(snooze_until("!snooze 99999999999y", "!", now), None);
+    }
+
+    #[test]
+    fn commands_are_case_insensitive() {
+        assert_eq!(command_name("!HELP", "!").as_deref(), Some("help"));
+        assert_eq!(command_name("  !Cancel 2", "!").as_deref(), Some("cancel"));
+        assert_eq!(command_name("", "!"), None);
+        assert_eq!(command_name("help", "!"), None);
+        assert_eq!(parse_cancel_command("!CANCEL 2", "!"), Some(2));
+        assert_eq!(
+            parse_snooze_command("!Snooze 1h", "!"),
+            Some("1h".to_string())
+        );
+        assert_eq!(
+            parse_timezone_command("!TimeZone Europe/Warsaw", "!"),
+            Some(chrono_tz::Europe::Warsaw)
+        );
+        let command = parse_reminder_command("!RemindMe EVERY 1d stretch", "!").unwrap();
+        assert!(command.recurring);
+        assert_eq!(command.date_str, "1d");
+    }
+
+    #[test]
+    fn routes_commands_with_a_custom_prefix() {
+        assert_eq!(command_name("?help", "?").as_deref(), Some("help"));
+        assert_eq!(command_name("!help", "?"), None);
+        assert_eq!(parse_cancel_command("?cancel 2", "?"), Some(2));
+        assert_eq!(parse_cancel_command("!cancel 2", "?"), None);
+        assert_eq!(
+            parse_snooze_command("?snooze 1h", "?"),
+            Some("1h".to_string())
+        );
+        assert!(is_list_alias("?remindme list", "?"));
+        let command = parse_reminder_command("?remindme 1d stretch", "?").unwrap();
+        assert_eq!(command.date_str, "1d");
+        assert_eq!(command.text.as_deref(), Some("stretch"));
+        assert!(parse_reminder_command("!remindme 1d stretch", "?").is_none());
+        // Regex characters in the prefix are matched literally.
+        assert!(parse_reminder_command("xremindme 1d", "?").is_none());
+        assert_eq!(
+            parse_edit_command("rm.edit 1 text", "rm."),
+            Some((1, "text".to_string()))
+        );
+        assert_eq!(parse_edit_command("rmxedit 1 text", "rm."), None);
+    }
+
+    #[test]
+    fn remindme_list_lists_reminders() {
+        assert!(is_list_alias("!remindme list", "!"));
+        assert!(is_list_alias(" !REMINDME  List ", "!"));
+        assert!(!is_list_alias("!remindme list of groceries", "!"));
+        assert!(!is_list_alias("!remindme 1d list", "!"));
+    }
+
+    #[test]
+    fn parses_edit_commands() {
+        assert_eq!(
+            parse_edit_command("!edit 2 new text", "!"),
+            Some((2, "new text".to_string()))
+        );
+        assert_eq!(
+            parse_edit_command("!edit 1 two\nlines", "!"),
+            Some((1, "two\nlines".to_string()))
+        );
+        assert_eq!(parse_edit_command("!edit 2", "!"), None);
+        assert_eq!(parse_edit_command("!edit last new text", "!"), None);
+    }
+
+    #[test]
+    fn parses_cancel_numbers() {
+        assert_eq!(parse_cancel_command("!cancel 3", "!"), Some(3));
+        assert_eq!(parse_cancel_command(" !cancel 12 ", "!"), Some(12));
+        assert_eq!(parse_cancel_command("!cancel x", "!"), None);
+        assert_eq!(parse_cancel_command("!cancel", "!"), None);
+    }
+
+    #[test]
+    fn reads_dates_in_the_users_timezone() {
+        let warsaw = Some(chrono_tz::Europe::Warsaw);
+        let now = utc(2024, 1, 1, 12, 0);
+        assert_eq!(
+            parse_date_str("2024-06-01-14-00", warsaw, now, nine_am(), &[]),
+            Ok(utc(2024, 6, 1, 12, 0))
+        );
+        // The offset in force on that date is used, on either side of
+        // Warsaw's DST change on 31 March.
+        assert_eq!(
+            parse_date_str("2024-03-30-12-00", warsaw, now, nine_am(), &[]),
+            Ok(utc(2024, 3, 30, 11, 0))
+        );
+        assert_eq!(
+            parse_date_str("2024-03-31-12-00", warsaw, now, nine_am(), &[]),
+            Ok(utc(2024, 3, 31, 10, 0))
+        );
+    }
+
+    #[test]
+    fn parses_cancel_by_text() {
+        assert_eq!(
+            parse_cancel_text_command("!cancel text Water the plants ", "!").as_deref(),
+            Some("Water the plants")
+        );
+        assert_eq!(
+            parse_cancel_text_command("!Cancel TEXT dentist", "!").as_deref(),
+            Some("dentist")
+        );
+        assert_eq!(parse_cancel_text_command("!cancel text", "!"), None);
+        assert_eq!(parse_cancel_text_command("!cancel 3", "!"), None);
+    }
+
+    #[test]
+    fn parses_timezone_names() {
+        assert_eq!(
+            parse_timezone_command("!timezone Europe/Warsaw", "!"),
+            Some(chrono_tz::Europe::Warsaw)
+        );
+        assert_eq!(parse_timezone_command("!timezone Mars/Olympus", "!"), None);
+        assert_eq!(parse_timezone_command("!timezone", "!"), None);
+    }
+
+    #[test]
+    fn parses_whenis_dates() {
+        assert_eq!(
+            parse_whenis_command("!whenis next friday 9am", "!").as_deref(),
+            Some("next friday 9am")
+        );
+        assert_eq!(
+            parse_whenis_command(" !WhenIs 2h ", "!").as_deref(),
+            Some("2h")
+        );
+        assert_eq!(parse_whenis_command("!whenis", "!"), None);
+    }
+
+    #[test]
+    fn parses_digest_switch() {
+        assert_eq!(parse_digest_command("!digest on", "!"), Some(true));
+        assert_eq!(parse_digest_command(" !Digest OFF ", "!"), Some(false));
+        assert_eq!(parse_digest_command("!digest", "!"), None);
+        assert_eq!(parse_digest_command("!digest maybe", "!"), None);
+    }
+
+    #[test]
+    fn parses_reschedule_commands() {
+        assert_eq!(
+            parse_reschedule_command("!reschedule 2 tomorrow 9am", "!"),
+            Some((2, "tomorrow 9am".to_string()))
+        );
+        assert_eq!(
+            parse_reschedule_command(" !Reschedule 1 2h ", "!"),
+            Some((1, "2h".to_string()))
+        );
+        assert_eq!(parse_reschedule_command("!reschedule 2", "!"), None);
+        assert_eq!(parse_reschedule_command("!reschedule two 2h", "!"), None);
+    }
+
+    #[test]
+    fn parses_upcoming_hours() {
+        assert_eq!(parse_upcoming_command("!upcoming 24", "!"), Some(24));
+        assert_eq!(parse_upcoming_command(" !UPCOMING 2 ", "!"), Some(2));
+        assert_eq!(parse_upcoming_command("!upcoming", "!"), None);
+        assert_eq!(parse_upcoming_command("!upcoming 2h", "!"), None);
+        assert_eq!(parse_upcoming_command("!upcoming 99999999999", "!"), None);
+    }
+
+    #[test]
+    fn parses_settings_commands() {
+        assert_eq!(
+            parse_settings_command("!settings", "!"),
+            Some(SettingsCommand::Show)
+        );
+        assert_eq!(
+            parse_settings_command("!Settings PREFIX ?", "!"),
+            Some(SettingsCommand::Prefix(Some("?".to_string())))
+        );
+        assert_eq!(
+            parse_settings_command("!settings prefix default", "!"),
+            Some(SettingsCommand::Prefix(None))
+        );
+        assert_eq!(
+            parse_settings_command("!settings limit 20", "!"),
+            Some(SettingsCommand::ReminderLimit(Some(20)))
+        );
+        assert_eq!(
+            parse_settings_command("!settings limit Default", "!"),
+            Some(SettingsCommand::ReminderLimit(None))
+        );
+        assert_eq!(parse_settings_command("!settings limit 0", "!"), None);
+        assert_eq!(parse_settings_command("!settings limit many", "!"), None);
+        assert_eq!(
+            parse_settings_command("!settings prefix remind:", "!"),
+            None
+        );
+        assert_eq!(parse_settings_command("!settings prefix ? !", "!"), None);
+        assert_eq!(parse_settings_command("!settings colour blue", "!"), None);
+    }
+
+    #[test]
+    fn parses_locale_codes() {
+        assert_eq!(
+            parse_locale_command("!locale pl", "!").as_deref(),
+            Some("pl")
+        );
+        assert_eq!(
+            parse_locale_command(" !LOCALE de-AT ", "!").as_deref(),
+            Some("de-AT")
+        );
+        assert_eq!(parse_locale_command("!locale", "!"), None);
+        assert_eq!(parse_locale_command("!locale pl de", "!"), None);
+    }
+
+    #[test]
+    fn explains_why_dates_cant_be_read() {
+        let now = utc(2024, 3, 6, 12, 0);
+        let parse = |date_str| {
+            parse_date_str(
+                date_str,
+                Some(chrono_tz::Europe::Warsaw),
+                now,
+                nine_am(),
+                &[],
+            )
+        };
+        assert_eq!(parse("someday"), Err(DateParseError::UnknownFormat));
+        assert_eq!(parse("9"), Err(DateParseError::UnknownFormat));
+        assert_eq!(parse("1d1d"), Err(DateParseError::UnknownFormat));
+        assert_eq!(parse("2024-02-30 12:00"), Err(DateParseError::OutOfRange));
+        assert_eq!(parse("25:00"), Err(DateParseError::OutOfRange));
+        assert_eq!(parse("13pm"), Err(DateParseError::OutOfRange));
+        // Clocks in Warsaw skip from 2:00 to 3:00 on 31 March.
+        assert_eq!(parse("2024-03-31 02:30"), Err(DateParseError::OutOfRange));
+        assert_eq!(parse("2024-03-01 12:00"), Err(DateParseError::InPast));
+        assert_eq!(parse("999999999999h"), Err(DateParseError::Overflow));
+        assert_eq!(
+            parse("99999999999999999999d"),
+            Err(DateParseError::Overflow)
+        );
+    }
+
+    #[test]
+    fn parses_unix_timestamps() {
+        let now = utc(2024, 3, 6, 12, 0);
+        assert_eq!(
+            parse_date_str("@1717251000", None, now, nine_am(), &[]),
+            Ok(utc(2024, 6, 1, 14, 10))
+        );
+        assert_eq!(
+            parse_date_str("@1709722800", None, now, nine_am(), &[]),
+            Err(DateParseError::InPast)
+        );
+        assert_eq!(
+            parse_date_str("@-1717251000", None, now, nine_am(), &[]),
+            Err(DateParseError::OutOfRange)
+        );
+        // Past the year 262143, chrono's limit.
+        assert_eq!(
+            parse_date_str("@9999999999999", None, now, nine_am(), &[]),
+            Err(DateParseError::Overflow)
+        );
+        assert_eq!(
+            parse_date_str("@99999999999999999999", None, now, nine_am(), &[]),
+            Err(DateParseError::Overflow)
+        );
+        assert_eq!(
+            parse_date_str("@soon", None, now, nine_am(), &[]),
+            Err(DateParseError::UnknownFormat)
+        );
+
+        let command = parse_reminder_command("!remindme @1717251000 deploy", "!").unwrap();
+        assert_eq!(command.date_str, "@1717251000");
+        assert_eq!(command.text.as_deref(), Some("deploy"));
+    }
+
+    #[test]
+    fn parses_week_and_month_durations() {
+        let now = Utc::now();
+        assert!(parse_date_str("2w", None, now, nine_am(), &[]).is_ok());
+        assert!(parse_date_str("3mo", None, now, nine_am(), &[]).is_ok());
+        assert!(parse_date_str("3x", None, now, nine_am(), &[]).is_err());
+    }
+
+    #[test]
+    fn parses_combined_durations() {
+        let now = utc(2024, 3, 6, 12, 0);
+        assert_eq!(add_duration(now, "1d12h30m"), Some(utc(2024, 3, 8, 0, 30)));
+        assert_eq!(add_duration(now, "30m1d"), Some(utc(2024, 3, 7, 12, 30)));
+        // Months by calendar, then the rest.
+        assert_eq!(add_duration(now, "1mo2d"), Some(utc(2024, 4, 8, 12, 0)));
+        assert_eq!(add_duration(now, "1d1d"), None);
+        assert_eq!(add_duration(now, "1d 12h"), None);
+        assert_eq!(add_duration(now, "1dx"), None);
+    }
+
+    #[test]
+    fn parses_second_durations() {
+        let now = utc(2024, 3, 6, 12, 0);
+        assert_eq!(
+            add_duration(now, "30s"),
+            Some(now + chrono::Duration::seconds(30))
+        );
+        assert_eq!(
+            add_duration(now, "1m30s"),
+            Some(now + chrono::Duration::seconds(90))
+        );
+        // `m` stays minutes next to `mo`.
+        assert_eq!(add_duration(now, "1mo1m"), Some(utc(2024, 4, 6, 12, 1)));
+        assert_eq!(add_duration(now, "5ms"), None);
+        assert_eq!(
+            parse_natural_date(&Utc, now, "in 45 seconds"),
+            Some(now + chrono::Duration::seconds(45))
+        );
+    }
+
+    #[test]
+    fn minutes_and_months_are_explicit() {
+        let now = utc(2024, 3, 6, 12, 0);
+        assert_eq!(add_duration(now, "5min"), Some(utc(2024, 3, 6, 12, 5)));
+        assert_eq!(add_duration(now, "5mo"), Some(utc(2024, 8, 6, 12, 0)));
+        assert_eq!(add_duration(now, "5m"), Some(utc(2024, 3, 6, 12, 5)));
+        assert_eq!(add_duration(now, "1h30min"), Some(utc(2024, 3, 6, 13, 30)));
+        assert_eq!(add_duration(now, "5m5min"), None);
+
+        assert!(uses_legacy_minutes("5m"));
+        assert!(uses_legacy_minutes("1h5m"));
+        assert!(uses_legacy_minutes("5m1h"));
+        assert!(!uses_legacy_minutes("5min"));
+        assert!(!uses_legacy_minutes("5mo"));
+        assert!(!uses_legacy_minutes("5mo1h"));
+        assert!(!uses_legacy_minutes("tomorrow"));
+    }
+
+    #[test]
+    fn adds_months_by_calendar() {
+        // Jan 31 lands on the last day of February, leap year or not.
+        let jan_31 = utc(2024, 1, 31, 12, 0);
+        assert_eq!(add_duration(jan_31, "1mo"), Some(utc(2024, 2, 29, 12, 0)));
+        assert_eq!(
+            add_duration(utc(2023, 1, 31, 12, 0), "1mo"),
+            Some(utc(2023, 2, 28, 12, 0))
+        );
+        assert_eq!(add_duration(jan_31, "13mo"), Some(utc(2025, 2, 28, 12, 0)));
+        assert_eq!(
+            add_duration(utc(2024, 2, 29, 12, 0), "3mo"),
+            Some(utc(2024, 5, 29, 12, 0))
+        );
+    }
+
+    #[test]
+    fn adds_weeks_across_february() {
+        // A week on from 25 February is 3 March in a leap year, 4 March otherwise.
+        assert_eq!(
+            add_duration(utc(2024, 2, 25, 12, 0), "1w"),
+            Some(utc(2024, 3, 3, 12, 0))
+        );
+        assert_eq!(
+            add_duration(utc(2023, 2, 25, 12, 0), "1w"),
+            Some(utc(2023, 3, 4, 12, 0))
+        );
+        assert_eq!(
+            add_duration(utc(2024, 2, 28, 12, 0), "2w"),
+            Some(utc(2024, 3, 13, 12, 0))
+        );
+    }
+
+    #[test]
+    fn durations_too_large_to_add_are_rejected() {
+        let now = utc(2024, 3, 6, 12, 0);
+        assert_eq!(add_duration(now, "999999999999h"), None);
+        assert_eq!(add_duration(now, "99999999999mo"), None);
+        assert_eq!(add_duration(now, "9999999999999999w"), None);
+        assert_eq!(add_duration(now, "9223372036854775807y"), None);
+        assert_eq!(add_duration(now, "99999999999999999999d"), None);
+    }
+
+    #[test]
+    fn parses_recurring_reminders() {
+        let command = parse_reminder_command("!remindme every 1d take vitamins", "!").unwrap();
+        assert_eq!(command.date_str, "1d");
+        assert_eq!(command.text.as_deref(), Some("take vitamins"));
+        assert!(command.recurring);
+
+        let command = parse_reminder_command("!remindme 1d", "!").unwrap();
+        assert_eq!(command.date_str, "1d");
+        assert_eq!(command.text, None);
+        assert!(!command.recurring);
+    }
+
+    #[test]
+    fn parses_bounded_repeats() {
+        let command =
+            parse_reminder_command("!remindme repeat 5 times every 2h drink water", "!").unwrap();
+        assert_eq!(command.repeat_count, Some(5));
+        assert!(command.recurring);
+        assert_eq!(command.date_str, "2h");
+        assert_eq!(command.text.as_deref(), Some("drink water"));
+
+        let command = parse_reminder_command("!remindme every 2h drink water", "!").unwrap();
+        assert_eq!(command.repeat_count, None);
+
+        // A count needs an interval to repeat on.
+        let command = parse_reminder_command("!remindme repeat 5 times 2h", "!").unwrap();
+        assert_eq!(command.repeat_count, None);
+        assert!(!command.recurring);
+    }
+
+    #[test]
+    fn keeps_every_line_of_the_message() {
+        let command =
+            parse_reminder_command("!remindme 1d shopping:\n- milk\n- eggs\n", "!").unwrap();
+        assert_eq!(command.date_str, "1d");
+        assert_eq!(command.text.as_deref(), Some("shopping:\n- milk\n- eggs\n"));
+
+        let command = parse_reminder_command("!remindme 1h\nstretch", "!").unwrap();
+        assert_eq!(command.date_str, "1h");
+        assert_eq!(command.text.as_deref(), Some("stretch"));
+    }
+
+    #[test]
+    fn skips_a_leading_preposition() {
+        let cases = [
+            ("!remindme in 2h stretch", "2h"),
+            ("!remindme 2h stretch", "2h"),
+            ("!remindme on 2024-06-01 12:30 stretch", "2024-06-01 12:30"),
+            ("!remindme 2024-06-01 12:30 stretch", "2024-06-01 12:30"),
+            ("!remindme At 9am stretch", "9am"),
+            ("!remindme 9am stretch", "9am"),
+        ];
+        for (message, date_str) in cases {
+            let command = parse_reminder_command(message, "!").unwrap();
+            assert_eq!(command.date_str, date_str, "{}", message);
+            assert_eq!(command.text.as_deref(), Some("stretch"), "{}", message);
+        }
+
+        let command = parse_reminder_command("!remindme dm in 1d", "!").unwrap();
+        assert!(command.deliver_dm);
+        assert_eq!(command.date_str, "1d");
+        assert_eq!(command.text, None);
+        // Nothing after the preposition still asks for usage.
+        assert_eq!(
+            parse_reminder_command("!remindme at", "!")
+                .unwrap()
+                .date_str,
+            ""
+        );
+    }
+
+    #[test]
+    fn parses_user_mention() {
+        let command = parse_reminder_command("!remindme <@123> 1h call me", "!").unwrap();
+        assert_eq!(command.target_user_id.as_deref(), Some("123"));
+        assert_eq!(command.date_str, "1h");
+        assert_eq!(command.text.as_deref(), Some("call me"));
+
+        let command = parse_reminder_command("!remindme <@!123> every 1w standup", "!").unwrap();
+        assert_eq!(command.target_user_id.as_deref(), Some("123"));
+        assert!(command.recurring);
+
+        // Only a mention before the date picks who gets reminded.
+        let command = parse_reminder_command("!remindme 1h ask <@123>", "!").unwrap();
+        assert_eq!(command.target_user_id, None);
+        assert_eq!(command.text.as_deref(), Some("ask <@123>"));
+    }
+
+    #[test]
+    fn parses_high_priority_flag() {
+        let command = parse_reminder_command("!remindme !high 1h deploy", "!").unwrap();
+        assert!(command.high_priority);
+        assert_eq!(command.date_str, "1h");
+        assert_eq!(command.text.as_deref(), Some("deploy"));
+
+        let command =
+            parse_reminder_command("!remindme !HIGH <@123> every 1d check alerts", "!").unwrap();
+        assert!(command.high_priority);
+        assert_eq!(command.target_user_id.as_deref(), Some("123"));
+        assert!(command.recurring);
+
+        let command = parse_reminder_command("?remindme !high in 2h deploy", "?").unwrap();
+        assert!(command.high_priority);
+        assert_eq!(command.date_str, "2h");
+
+        let command = parse_reminder_command("!remindme 1h !high deploy", "!").unwrap();
+        assert!(!command.high_priority);
+        assert_eq!(command.text.as_deref(), Some("!high deploy"));
+    }
+
+    #[test]
+    fn splits_a_trailing_tag() {
+        assert_eq!(
+            split_tag("!remindme 1d send the report #Work"),
+            ("!remindme 1d send the report", Some("work".to_string()))
+        );
+        assert_eq!(
+            split_tag("!remindme 1d #side-project "),
+            ("!remindme 1d", Some("side-project".to_string()))
+        );
+        assert_eq!(
+            split_tag("!remindme 1d #1 fan"),
+            ("!remindme 1d #1 fan", None)
+        );
+        assert_eq!(
+            split_tag("!remindme <#456> 1d"),
+            ("!remindme <#456> 1d", None)
+        );
+        assert_eq!(split_tag("#work"), ("#work", None));
+
+        let command = parse_reminder_command("!remindme 1d send the report #work", "!").unwrap();
+        assert_eq!(command.date_str, "1d");
+        assert_eq!(command.text.as_deref(), Some("send the report"));
+        assert_eq!(command.tag.as_deref(), Some("work"));
+
+        let command = parse_reminder_command("!remindme in 2h #home", "!").unwrap();
+        assert_eq!(command.date_str, "2h");
+        assert_eq!(command.text, None);
+        assert_eq!(command.tag.as_deref(), Some("home"));
+
+        let command = parse_reminder_command("!remindme 1d water the plants", "!").unwrap();
+        assert_eq!(command.tag, None);
+    }
+
+    #[test]
+    fn parses_reminders_tag() {
+        assert_eq!(
+            parse_reminders_tag("!reminders #Work", "!").as_deref(),
+            Some("work")
+        );
+        assert_eq!(
+            parse_reminders_tag(" !reminders home ", "!").as_deref(),
+            Some("home")
+        );
+        assert_eq!(parse_reminders_tag("!reminders", "!"), None);
+        assert_eq!(parse_reminders_tag("!reminders work home", "!"), None);
+    }
+
+    #[test]
+    fn parses_role_mention() {
+        let command = parse_reminder_command("!remindme <@&789> 1h standup", "!").unwrap();
+        assert_eq!(command.target_role_id.as_deref(), Some("789"));
+        assert_eq!(command.target_user_id, None);
+        assert_eq!(command.date_str, "1h");
+        assert_eq!(command.text.as_deref(), Some("standup"));
+
+        let command =
+            parse_reminder_command("!remindme <@&789> <#456> every 1w standup", "!").unwrap();
+        assert_eq!(command.target_role_id.as_deref(), Some("789"));
+        assert_eq!(command.channel_id.as_deref(), Some("456"));
+        assert!(command.recurring);
+
+        // A role mentioned after the date is just part of the message.
+        let command = parse_reminder_command("!remindme 1h ask <@&789>", "!").unwrap();
+        assert_eq!(command.target_role_id, None);
+        assert_eq!(command.text.as_deref(), Some("ask <@&789>"));
+
+        // A user and a role can't both be pinged.
+        let command = parse_reminder_command("!remindme <@123> <@&789> 1h", "!").unwrap();
+        assert_eq!(command.target_role_id, None);
+        assert_eq!(command.date_str, "<@&789>");
+    }
+
+    #[test]
+    fn parses_channel_mention() {
+        let command = parse_reminder_command("!remindme <#456> 1h standup", "!").unwrap();
+        assert_eq!(command.channel_id.as_deref(), Some("456"));
+        assert_eq!(command.date_str, "1h");
+        assert_eq!(command.text.as_deref(), Some("standup"));
+
+        let command =
+            parse_reminder_command("!remindme <@123> <#456> every 1w standup", "!").unwrap();
+        assert_eq!(command.target_user_id.as_deref(), Some("123"));
+        assert_eq!(command.channel_id.as_deref(), Some("456"));
+        assert!(command.recurring);
+
+        // A channel mentioned after the date is just part of the message.
+        let command = parse_reminder_command("!remindme 1h post in <#456>", "!").unwrap();
+        assert_eq!(command.channel_id, None);
+        assert_eq!(command.text.as_deref(), Some("post in <#456>"));
+    }
+
+    #[test]
+    fn parses_dm_flag() {
+        let command = parse_reminder_command("!remindme <@123> DM every 2h stretch", "!").unwrap();
+        assert!(command.deliver_dm);
+        assert!(command.recurring);
+        assert_eq!(command.target_user_id.as_deref(), Some("123"));
+        assert_eq!(command.date_str, "2h");
+
+        let command = parse_reminder_command("!remindme 1h dm mom", "!").unwrap();
+        assert!(!command.deliver_dm);
+        assert_eq!(command.text.as_deref(), Some("dm mom"));
+    }
+
+    #[test]
+    fn bare_remindme_asks_for_usage() {
+        for message in ["!remindme", "!remindme   "] {
+            let command = parse_reminder_command(message, "!").unwrap();
+            assert_eq!(command.date_str, "");
+            assert_eq!(command.text, None);
+        }
+        assert!(parse_reminder_command("!remindmeplease", "!").is_none());
+    }
+
+    #[test]
+    fn only_absolute_dates_can_be_in_the_past() {
+        let now = Utc::now();
+        assert_eq!(
+            parse_date_str("2020-01-01-00-00", None, now, nine_am(), &[]),
+            Err(DateParseError::InPast)
+        );
+        for date_str in ["1m", "2h", "9am", "23:59"] {
+            assert!(
+                parse_date_str(date_str, None, now, nine_am(), &[]).is_ok_and(|time| time > now)
+            );
+        }
+    }
+
+    #[test]
+    fn parses_iso_style_dates() {
+        let expected = chrono::NaiveDate::from_ymd_opt(2024, 6, 1)
+            .unwrap()
+            .and_hms_opt(12, 30, 0);
+        for date_str in [
+            "2024-06-01 12:30",
+            "2024-06-01T12:30",
+            "2024-06-01 12:30:00",
+            "2024-06-01T12:30:00",
+            "2024-06-01-12-30",
+        ] {
+            assert_eq!(
+                parse_absolute_datetime(date_str, nine_am()),
+                expected,
+                "{}",
+                date_str
+            );
+        }
+        assert_eq!(parse_absolute_datetime("2024-02-30 12:00", nine_am()), None);
+        assert_eq!(parse_absolute_datetime("2024-02-30", nine_am()), None);
+    }
+
+    #[test]
+    fn dates_without_a_time_default_to_the_configured_time() {
+        let warsaw = Some(chrono_tz::Europe::Warsaw);
+        let now = utc(2024, 1, 1, 12, 0);
+        assert_eq!(
+            parse_date_str("2024-06-01", warsaw, now, nine_am(), &[]),
+            Ok(utc(2024, 6, 1, 7, 0))
+        );
+        let noon = NaiveTime::from_hms_opt(12, 0, 0).unwrap();
+        assert_eq!(
+            parse_date_str("2024-06-01", None, now, noon, &[]),
+            parse_date_str("2024-06-01 12:00", None, now, nine_am(), &[])
+        );
+        // A time given with the date still wins.
+        assert_eq!(
+            parse_date_str("2024-06-01 14:00", warsaw, now, nine_am(), &[]),
+            Ok(utc(2024, 6, 1, 12, 0))
+        );
+        assert_eq!(
+            parse_date_str("2024-02-30", warsaw, now, nine_am(), &[]),
+            Err(DateParseError::OutOfRange)
+        );
+
+        let command = parse_reminder_command("!remindme 2024-06-01 buy cake", "!").unwrap();
+        assert_eq!(command.date_str, "2024-06-01");
+        assert_eq!(command.text.as_deref(), Some("buy cake"));
+    }
+
+    #[test]
+    fn reads_dates_with_a_space_from_the_command() {
+        let command = parse_reminder_command("!remindme 2024-06-01 12:30 dentist", "!").unwrap();
+        assert_eq!(command.date_str, "2024-06-01 12:30");
+        assert_eq!(command.text.as_deref(), Some("dentist"));
+    }
+
+    #[test]
+    fn parses_natural_dates() {
+        // A Wednesday.
+        let now = utc(2024, 3, 6, 12, 0);
+        let parse = |date_str| parse_natural_date(&Utc, now, date_str);
+        assert_eq!(parse("in 2 hours"), Some(utc(2024, 3, 6, 14, 0)));
+        assert_eq!(parse("in an hour"), Some(utc(2024, 3, 6, 13, 0)));
+        assert_eq!(parse("tomorrow"), Some(utc(2024, 3, 7, 12, 0)));
+        assert_eq!(parse("Tomorrow 9am"), Some(utc(2024, 3, 7, 9, 0)));
+        assert_eq!(parse("today at 17:30"), Some(utc(2024, 3, 6, 17, 30)));
+        assert_eq!(parse("next friday 9am"), Some(utc(2024, 3, 8, 9, 0)));
+        assert_eq!(parse("wednesday"), Some(utc(2024, 3, 13, 12, 0)));
+        assert_eq!(parse("today"), None);
+        assert_eq!(parse("someday"), None);
+        assert_eq!(parse("in 2 fortnights"), None);
+    }
+
+    #[test]
+    fn rejects_natural_dates_in_the_past() {
+        let now = utc(2024, 3, 6, 15, 0);
+        let parse = |date_str| parse_date_str(date_str, Some(chrono_tz::UTC), now, nine_am(), &[]);
+        assert_eq!(parse("today 9am"), Err(DateParseError::InPast));
+        assert_eq!(parse("today at 17:30"), Ok(utc(2024, 3, 6, 17, 30)));
+        assert_eq!(parse("tomorrow 9am"), Ok(utc(2024, 3, 7, 9, 0)));
+    }
+
+    #[test]
+    fn business_days_skip_weekends() {
+        let parse =
+            |date_str, now| parse_date_str(date_str, Some(chrono_tz::UTC), now, nine_am(), &[]);
+        // Friday 8 March 2024.
+        let friday = utc(2024, 3, 8, 15, 0);
+        assert_eq!(parse("1bd", friday), Ok(utc(2024, 3, 11, 15, 0)));
+        assert_eq!(parse("3BD", friday), Ok(utc(2024, 3, 13, 15, 0)));
+        assert_eq!(parse("5bd", friday), Ok(utc(2024, 3, 15, 15, 0)));
+        assert_eq!(parse("6bd", friday), Ok(utc(2024, 3, 18, 15, 0)));
+        // From a weekend, the first business day is Monday.
+        assert_eq!(
+            parse("1bd", utc(2024, 3, 9, 10, 0)),
+            Ok(utc(2024, 3, 11, 10, 0))
+        );
+        assert_eq!(
+            parse("5bd", utc(2024, 3, 10, 10, 0)),
+            Ok(utc(2024, 3, 15, 10, 0))
+        );
+        // Already Saturday in Warsaw, where Monday 00:30 is 23:30 UTC.
+        assert_eq!(
+            parse_date_str(
+                "1bd",
+                Some(chrono_tz::Europe::Warsaw),
+                utc(2024, 3, 8, 23, 30),
+                nine_am(),
+                &[]
+            ),
+            Ok(utc(2024, 3, 10, 23, 30))
+        );
+        assert_eq!(parse("0bd", friday), Err(DateParseError::UnknownFormat));
+        assert_eq!(parse("1bd2h", friday), Err(DateParseError::UnknownFormat));
+        assert_eq!(parse("4294967295bd", friday), Err(DateParseError::Overflow));
+    }
+
+    #[test]
+    fn business_days_skip_holidays() {
+        let date = |month, day| NaiveDate::from_ymd_opt(2024, month, day).unwrap();
+        // Friday 29 March to Monday 1 April 2024, Easter Monday.
+        let easter_monday = [date(4, 1)];
+        let parse = |date_str, holidays: &[NaiveDate]| {
+            parse_date_str(
+                date_str,
+                Some(chrono_tz::UTC),
+                utc(2024, 3, 29, 15, 0),
+                nine_am(),
+                holidays,
+            )
+        };
+        assert_eq!(parse("1bd", &easter_monday), Ok(utc(2024, 4, 2, 15, 0)));
+        assert_eq!(parse("1bd", &[]), Ok(utc(2024, 4, 1, 15, 0)));
+        // Holidays back to back, and ones on a weekend or before today that
+        // don't matter.
+        let holidays = [date(3, 28), date(3, 30), date(4, 1), date(4, 2), date(4, 5)];
+        assert_eq!(parse("1bd", &holidays), Ok(utc(2024, 4, 3, 15, 0)));
+        assert_eq!(parse("3bd", &holidays), Ok(utc(2024, 4, 8, 15, 0)));
+
+        assert_eq!(
+            add_business_days(date(4, 1), 1, &easter_monday),
+            Some(date(4, 2))
+        );
+    }
+
+    #[test]
+    fn reads_natural_dates_in_the_users_timezone() {
+        let now = utc(2024, 3, 6, 12, 0);
+        assert_eq!(
+            parse_natural_date(&chrono_tz::Europe::Warsaw, now, "tomorrow 9am"),
+            Some(utc(2024, 3, 7, 8, 0))
+        );
+    }
+
+    #[test]
+    fn natural_dates_are_read_whole_from_the_command() {
+        let command = parse_reminder_command("!remindme next friday 9am standup", "!").unwrap();
+        assert_eq!(command.date_str, "next friday 9am");
+        assert_eq!(command.text.as_deref(), Some("standup"));
+
+        let command = parse_reminder_command("!remindme in 2 hours call mom", "!").unwrap();
+        assert_eq!(command.date_str, "in 2 hours");
+        assert_eq!(command.text.as_deref(), Some("call mom"));
+    }
+
+    #[test]
+    fn parses_times_of_day() {
+        let time = |hour, minute| NaiveTime::from_hms_opt(hour, minute, 0);
+        assert_eq!(parse_time_of_day("17:30"), time(17, 30));
+        assert_eq!(parse_time_of_day("9am"), time(9, 0));
+        assert_eq!(parse_time_of_day("9:15PM"), time(21, 15));
+        assert_eq!(parse_time_of_day("12am"), time(0, 0));
+        assert_eq!(parse_time_of_day("12pm"), time(12, 0));
+        assert_eq!(parse_time_of_day("13pm"), None);
+        assert_eq!(parse_time_of_day("25:00"), None);
+        assert_eq!(parse_time_of_day("9"), None);
+    }
+
+    #[test]
+    fn times_of_day_fire_at_their_next_occurrence() {
+        let now = utc(2024, 3, 6, 12, 0);
+        let time = |hour, minute| NaiveTime::from_hms_opt(hour, minute, 0).unwrap();
+        assert_eq!(
+            next_time_of_day(&Utc, now, time(17, 30)),
+            Some(utc(2024, 3, 6, 17, 30))
+        );
+        assert_eq!(
+            next_time_of_day(&Utc, now, time(9, 0)),
+            Some(utc(2024, 3, 7, 9, 0))
+        );
+        assert_eq!(
+            next_time_of_day(&Utc, now, time(12, 0)),
+            Some(utc(2024, 3, 7, 12, 0))
+        );
+        // 9am in Warsaw is 8am UTC before the clocks change.
+        assert_eq!(
+            next_time_of_day(&chrono_tz::Europe::Warsaw, now, time(9, 0)),
+            Some(utc(2024, 3, 7, 8, 0))
+        );
+    }
+
+    #[test]
+    fn daily_reminders_repeat_a_day_after_firing() {
+        let due = utc(2024, 3, 6, 9, 0);
+        // Sent on the next check, a little after it was due.
+        let fired = utc(2024, 3, 6, 9, 1);
+        assert_eq!(
+            next_recurrence_after("1d", due, fired, None),
+            Some(utc(2024, 3, 7, 9, 0))
+        );
+    }
+
+    #[test]
+    fn skips_occurrences_missed_while_offline() {
+        let now = utc(2024, 3, 6, 12, 0);
+        // A day offline with a rule every minute.
+        assert_eq!(
+            next_recurrence_after("1m", utc(2024, 3, 5, 12, 0), now, None),
+            Some(utc(2024, 3, 6, 12, 1))
+        );
+        assert_eq!(
+            next_recurrence_after("2h", utc(2024, 3, 5, 13, 0), now, None),
+            Some(utc(2024, 3, 6, 13, 0))
+        );
+        // Months still go by the calendar.
+        assert_eq!(
+            next_recurrence_after("1mo", utc(2024, 1, 31, 12, 0), now, None),
+            Some(utc(2024, 3, 29, 12, 0))
+        );
+    }
+
+    #[test]
+    fn weekday_reminders_repeat_the_following_week() {
+        let warsaw = Some(chrono_tz::Europe::Warsaw);
+        // Monday 4 March 2024, 9:00 in Warsaw.
+        let due = utc(2024, 3, 4, 8, 0);
+        assert_eq!(
+            next_recurrence_after("monday 9am", due, utc(2024, 3, 4, 8, 1), warsaw),
+            Some(utc(2024, 3, 11, 8, 0))
+        );
+        // Still 9:00 local after the clocks change on 31 March.
+        assert_eq!(
+            next_recurrence_after(
+                "monday at 9:00",
+                utc(2024, 3, 25, 8, 0),
+                utc(2024, 3, 25, 8, 1),
+                warsaw
+            ),
+            Some(utc(2024, 4, 1, 7, 0))
+        );
+        // Set on a Monday before the time, the first one is the same day.
+        let now = utc(2024, 3, 4, 6, 0);
+        assert_eq!(
+            next_recurrence_after("Monday 9am", now, now, warsaw),
+            Some(utc(2024, 3, 4, 8, 0))
+        );
+        // A week offline only skips ahead to the next one.
+        assert_eq!(
+            next_recurrence_after("monday 9am", due, utc(2024, 3, 20, 12, 0), warsaw),
+            Some(utc(2024, 3, 25, 8, 0))
+        );
+        assert_eq!(next_recurrence_after("monday", due, due, warsaw), None);
+        assert_eq!(next_recurrence_after("someday 9am", due, due, warsaw), None);
+
+        let command = parse_reminder_command("!remindme every monday 9am standup", "!").unwrap();
+        assert!(command.recurring);
+        assert_eq!(command.date_str, "monday 9am");
+        assert_eq!(command.text.as_deref(), Some("standup"));
+    }
+
+    #[test]
+    fn cron_reminders_fire_at_the_next_matching_time() {
+        let warsaw = Some(chrono_tz::Europe::Warsaw);
+        let rule = r#"cron "0 9 * * 1-5""#;
+        // Friday 8 March 2024, 9:00 in Warsaw, next up is Monday.
+        let due = utc(2024, 3, 8, 8, 0);
+        assert_eq!(
+            next_recurrence_after(rule, due, utc(2024, 3, 8, 8, 1), warsaw),
+            Some(utc(2024, 3, 11, 8, 0))
+        );
+        // Offline for days only skips ahead to the next one.
+        assert_eq!(
+            next_recurrence_after(rule, due, utc(2024, 3, 13, 12, 0), warsaw),
+            Some(utc(2024, 3, 14, 8, 0))
+        );
+        // 9:00 local is 7:00 UTC once the clocks change on 31 March.
+        assert_eq!(
+            next_recurrence_after(rule, utc(2024, 3, 29, 8, 0), utc(2024, 3, 29, 8, 1), warsaw),
+            Some(utc(2024, 4, 1, 7, 0))
+        );
+        // Day of week 0 is Sunday, as in crontab.
+        assert_eq!(
+            next_recurrence_after(r#"cron "*/15 * * * 0""#, due, due, Some(chrono_tz::UTC)),
+            Some(utc(2024, 3, 10, 0, 0))
+        );
+
+        let command =
+            parse_reminder_command(r#"!remindme cron "0 9 * * 1-5" standup"#, "!").unwrap();
+        assert!(command.recurring);
+        assert_eq!(command.date_str, r#"cron "0 9 * * 1-5""#);
+        assert_eq!(command.text.as_deref(), Some("standup"));
+    }
+
+    #[test]
+    fn rejects_invalid_cron_expressions() {
+        assert!(parse_cron("0 9 * * 1-5").is_some());
+        assert!(parse_cron("30 17 1,15 * sun").is_some());
+        assert!(parse_cron("0 9 * *").is_none());
+        assert!(parse_cron("0 0 9 * * 1-5").is_none());
+        assert!(parse_cron("60 9 * * *").is_none());
+        assert!(parse_cron("0 9 * * 8").is_none());
+        assert!(parse_cron("0 9 30 2 *").is_none());
+        assert_eq!(cron_expression(r#"cron "0 9 * * *""#), Some("0 9 * * *"));
+        assert_eq!(cron_expression("cron 0 9 * * *"), None);
+    }
+
+    #[test]
+    fn rejects_recurrences_more_often_than_a_minute() {
+        let now = utc(2024, 3, 6, 12, 0);
+        for interval in ["0m", "0d", "0mo", "tomorrow"] {
+            assert_eq!(next_recurrence_after(interval, now, now, None), None);
+        }
+        assert_eq!(
+            next_recurrence_after("1m", now, now, None),
+            Some(utc(2024, 3, 6, 12, 1))
+        );
+    }
+}
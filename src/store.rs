@@ -0,0 +1,814 @@
+//! Every query the bot runs, kept together so the SQL can be reviewed in one
+//! place and reached by the integration tests.
+
+use chrono::{DateTime, NaiveDate, Utc};
+use chrono_tz::Tz;
+use serde::Serialize;
+use sqlx::{FromRow, PgExecutor, PgPool};
+
+#[derive(Debug, FromRow)]
+pub struct Reminder {
+    pub id: Option<i32>,
+    pub user_id: String,
+    pub target_user_id: String,
+    pub message_id: String,
+    pub message_content: String,
+    pub trigger_time: DateTime<Utc>,
+    pub channel_id: String,
+    pub recurrence_interval: Option<String>,
+    pub status: String,
+    pub attempts: i32,
+    pub deliver_dm: bool,
+    pub remaining_occurrences: Option<i32>,
+    /// Filled in by the database, unknown for reminders older than the column.
+    pub created_at: Option<DateTime<Utc>>,
+    /// A role pinged instead of the target user, who is then the author.
+    pub target_role_id: Option<String>,
+    /// `PRIORITY_HIGH` reminders fire before others due at the same time.
+    pub priority: i16,
+    /// Set instead of removing the row, which stays until purged.
+    pub deleted_at: Option<DateTime<Utc>>,
+    /// Lowercased `#tag` it was set with, for filtering the list.
+    pub tag: Option<String>,
+    /// Set after a failed attempt, the reminder isn't due again before it.
+    pub retry_at: Option<DateTime<Utc>>,
+    /// `SOURCE_TEXT` or `SOURCE_SLASH`, unknown for older reminders.
+    pub source: Option<String>,
+}
+
+/// Every column `Reminder` is read from, checked at startup so a missing
+/// migration is reported up front instead of failing queries later.
+pub const REMINDER_COLUMNS: &[&str] = &[
+    "id",
+    "user_id",
+    "target_user_id",
+    "message_id",
+    "message_content",
+    "trigger_time",
+    "channel_id",
+    "recurrence_interval",
+    "status",
+    "attempts",
+    "deliver_dm",
+    "remaining_occurrences",
+    "created_at",
+    "target_role_id",
+    "priority",
+    "deleted_at",
+    "tag",
+    "retry_at",
+    "source",
+];
+
+pub async fn reminder_table_columns(pool: &PgPool) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT column_name::text AS "column_name!" FROM information_schema.columns
+        WHERE table_schema = current_schema() AND table_name = 'reminders'
+        "#
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// The `expected` columns that aren't among the `actual` ones, in order.
+pub fn missing_columns<'a>(expected: &[&'a str], actual: &[String]) -> Vec<&'a str> {
+    expected
+        .iter()
+        .copied()
+        .filter(|column| !actual.iter().any(|actual| actual == column))
+        .collect()
+}
+
+pub const STATUS_PENDING: &str = "pending";
+pub const STATUS_SENT: &str = "sent";
+pub const STATUS_FAILED: &str = "failed";
+
+pub const PRIORITY_NORMAL: i16 = 0;
+pub const PRIORITY_HIGH: i16 = 1;
+
+pub const SOURCE_TEXT: &str = "text";
+pub const SOURCE_SLASH: &str = "slash";
+
+/// `false` when a reminder for the same message already exists.
+pub async fn insert_reminder(
+    executor: impl PgExecutor<'_>,
+    reminder: &Reminder,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO reminders (user_id, target_user_id, message_id, message_content, trigger_time, channel_id, recurrence_interval, status, deliver_dm, remaining_occurrences, source, target_role_id, priority, tag)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+        ON CONFLICT (message_id) DO NOTHING
+        "#,
+        reminder.user_id,
+        reminder.target_user_id,
+        reminder.message_id,
+        reminder.message_content,
+        reminder.trigger_time,
+        reminder.channel_id,
+        reminder.recurrence_interval,
+        reminder.status,
+        reminder.deliver_dm,
+        reminder.remaining_occurrences,
+        reminder.source,
+        reminder.target_role_id,
+        reminder.priority,
+        reminder.tag
+    )
+    .execute(executor)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn get_due_reminders(pool: &PgPool) -> Result<Vec<Reminder>, sqlx::Error> {
+    let now = Utc::now();
+    let reminders = sqlx::query_as!(
+        Reminder,
+        r#"
+        SELECT * FROM reminders
+        WHERE trigger_time < $1 AND status = $2 AND deleted_at IS NULL
+            AND (retry_at IS NULL OR retry_at <= $1)
+        ORDER BY priority DESC, trigger_time
+        "#,
+        now,
+        STATUS_PENDING
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(reminders)
+}
+
+pub async fn count_user_reminders(pool: &PgPool, user_id: &str) -> Result<i64, sqlx::Error> {
+    let count = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) AS "count!" FROM reminders WHERE user_id = $1 AND status = $2"#,
+        user_id,
+        STATUS_PENDING
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(count)
+}
+
+pub async fn get_user_reminders(
+    pool: &PgPool,
+    user_id: &str,
+) -> Result<Vec<Reminder>, sqlx::Error> {
+    let reminders = sqlx::query_as!(
+        Reminder,
+        r#"SELECT * FROM reminders WHERE user_id = $1 AND status = $2 ORDER BY trigger_time ASC"#,
+        user_id,
+        STATUS_PENDING
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(reminders)
+}
+
+pub async fn resolve_user_reminder(
+    pool: &PgPool,
+    user_id: &str,
+    index: usize,
+) -> Result<Option<Reminder>, sqlx::Error> {
+    let reminders = get_user_reminders(pool, user_id).await?;
+    let reminder = index
+        .checked_sub(1)
+        .and_then(|i| reminders.into_iter().nth(i))
+        .filter(|reminder| reminder.user_id == user_id);
+    Ok(reminder)
+}
+
+/// `false` when the reminder fired, was cancelled or isn't the user's.
+pub async fn update_reminder_text(
+    pool: &PgPool,
+    user_id: &str,
+    id: i32,
+    text: &str,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE reminders SET message_content = $1
+        WHERE id = $2 AND user_id = $3 AND status = $4
+        "#,
+        text,
+        id,
+        user_id,
+        STATUS_PENDING
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// Moves a pending reminder to `new_time`, `false` when it fired, was
+/// cancelled or isn't the user's.
+pub async fn reschedule_reminder(
+    pool: &PgPool,
+    user_id: &str,
+    id: i32,
+    new_time: DateTime<Utc>,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE reminders SET trigger_time = $1, retry_at = NULL
+        WHERE id = $2 AND user_id = $3 AND status = $4
+        "#,
+        new_time,
+        id,
+        user_id,
+        STATUS_PENDING
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+pub async fn cancel_reminder_for_user(
+    pool: &PgPool,
+    user_id: &str,
+    index: usize,
+) -> Result<Option<Reminder>, sqlx::Error> {
+    let Some(reminder) = resolve_user_reminder(pool, user_id, index).await? else {
+        return Ok(None);
+    };
+    delete_user_reminder(pool, user_id, reminder).await
+}
+
+/// The user's most recently set reminder that is still pending.
+pub async fn last_user_reminder(
+    pool: &PgPool,
+    user_id: &str,
+) -> Result<Option<Reminder>, sqlx::Error> {
+    let reminder = sqlx::query_as!(
+        Reminder,
+        r#"SELECT * FROM reminders WHERE user_id = $1 AND status = $2 ORDER BY id DESC LIMIT 1"#,
+        user_id,
+        STATUS_PENDING
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(reminder)
+}
+
+pub async fn undo_last_reminder(
+    pool: &PgPool,
+    user_id: &str,
+) -> Result<Option<Reminder>, sqlx::Error> {
+    let Some(reminder) = last_user_reminder(pool, user_id).await? else {
+        return Ok(None);
+    };
+    delete_user_reminder(pool, user_id, reminder).await
+}
+
+/// The user's pending reminders whose text contains `fragment`, ignoring case.
+pub async fn find_user_reminders_by_text(
+    pool: &PgPool,
+    user_id: &str,
+    fragment: &str,
+) -> Result<Vec<Reminder>, sqlx::Error> {
+    // `%` and `_` in the fragment are matched literally.
+    let pattern = format!(
+        "%{}%",
+        fragment
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_")
+    );
+    let reminders = sqlx::query_as!(
+        Reminder,
+        r#"
+        SELECT * FROM reminders
+        WHERE user_id = $1 AND status = $2 AND message_content ILIKE $3
+        ORDER BY trigger_time ASC
+        "#,
+        user_id,
+        STATUS_PENDING,
+        pattern
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(reminders)
+}
+
+/// `None` when the reminder fired in the meantime or isn't the user's.
+pub async fn delete_user_reminder(
+    pool: &PgPool,
+    user_id: &str,
+    reminder: Reminder,
+) -> Result<Option<Reminder>, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"DELETE FROM reminders WHERE id = $1 AND user_id = $2 AND status = $3"#,
+        reminder.id,
+        user_id,
+        STATUS_PENDING
+    )
+    .execute(pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Ok(None);
+    }
+    Ok(Some(reminder))
+}
+
+/// Only ever touches the caller's own pending reminders.
+pub async fn cancel_all_reminders_for_user(
+    pool: &PgPool,
+    user_id: &str,
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"DELETE FROM reminders WHERE user_id = $1 AND status = $2"#,
+        user_id,
+        STATUS_PENDING
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+pub async fn claim_reminder(pool: &PgPool, id: i32) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"UPDATE reminders SET status = $1 WHERE id = $2 AND status = $3"#,
+        STATUS_SENT,
+        id,
+        STATUS_PENDING
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// `true` when the reminder ran out of attempts, otherwise it's tried again
+/// from `retry_at`.
+pub async fn record_failed_attempt(
+    pool: &PgPool,
+    id: i32,
+    max_send_attempts: i32,
+    retry_at: DateTime<Utc>,
+) -> Result<bool, sqlx::Error> {
+    let status = sqlx::query_scalar!(
+        r#"
+        UPDATE reminders
+        SET attempts = attempts + 1,
+            status = CASE WHEN attempts + 1 >= $1 THEN $2 ELSE $3 END,
+            retry_at = $4
+        WHERE id = $5
+        RETURNING status
+        "#,
+        max_send_attempts,
+        STATUS_FAILED,
+        STATUS_PENDING,
+        retry_at,
+        id
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(status == STATUS_FAILED)
+}
+
+/// For rows that could never be sent, so they aren't picked up again.
+pub async fn mark_reminder_failed(pool: &PgPool, id: i32) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"UPDATE reminders SET status = $1 WHERE id = $2"#,
+        STATUS_FAILED,
+        id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Only marks the reminder deleted, so there's a record of what went out
+/// until `purge_deleted_reminders` removes it.
+pub async fn delete_reminder(pool: &PgPool, id: i32) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"UPDATE reminders SET deleted_at = $1 WHERE id = $2 AND deleted_at IS NULL"#,
+        Utc::now(),
+        id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Removes reminders marked deleted before `before`, returning how many.
+pub async fn purge_deleted_reminders(
+    pool: &PgPool,
+    before: DateTime<Utc>,
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(r#"DELETE FROM reminders WHERE deleted_at < $1"#, before)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected())
+}
+
+pub async fn set_next_trigger_time(
+    pool: &PgPool,
+    id: i32,
+    trigger_time: DateTime<Utc>,
+    remaining_occurrences: Option<i32>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"UPDATE reminders SET trigger_time = $1, status = $2, remaining_occurrences = $3 WHERE id = $4"#,
+        trigger_time,
+        STATUS_PENDING,
+        remaining_occurrences,
+        id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn record_sent_reminder(pool: &PgPool, reminder: &Reminder) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO sent_reminders (user_id, message_id, message_content, channel_id, sent_at)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (user_id) DO UPDATE SET
+            message_id = EXCLUDED.message_id,
+            message_content = EXCLUDED.message_content,
+            channel_id = EXCLUDED.channel_id,
+            sent_at = EXCLUDED.sent_at
+        "#,
+        reminder.target_user_id,
+        reminder.message_id,
+        reminder.message_content,
+        reminder.channel_id,
+        Utc::now()
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// The snoozed reminder is keyed by the `snooze` command's message, the fired
+/// one may still be pending under its own if it repeats.
+pub async fn snooze_last_reminder(
+    pool: &PgPool,
+    user_id: &str,
+    message_id: &str,
+    trigger_time: DateTime<Utc>,
+) -> Result<bool, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let sent = sqlx::query!(
+        r#"
+        SELECT user_id, message_id, message_content, channel_id FROM sent_reminders
+        WHERE user_id = $1
+        ORDER BY sent_at DESC
+        LIMIT 1
+        FOR UPDATE
+        "#,
+        user_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let Some(sent) = sent else {
+        return Ok(false);
+    };
+    // Taking the record out means a snoozed reminder can only be snoozed again
+    // once it has fired again.
+    sqlx::query!(
+        r#"DELETE FROM sent_reminders WHERE user_id = $1 AND message_id = $2"#,
+        sent.user_id,
+        sent.message_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let reminder = Reminder {
+        id: None,
+        user_id: sent.user_id.clone(),
+        target_user_id: sent.user_id,
+        channel_id: sent.channel_id,
+        message_id: message_id.to_string(),
+        message_content: sent.message_content,
+        trigger_time,
+        recurrence_interval: None,
+        status: STATUS_PENDING.to_string(),
+        attempts: 0,
+        deliver_dm: false,
+        remaining_occurrences: None,
+        created_at: None,
+        // Snoozing is only done with the text command.
+        target_role_id: None,
+        priority: PRIORITY_NORMAL,
+        deleted_at: None,
+        tag: None,
+        retry_at: None,
+        source: Some(SOURCE_TEXT.to_string()),
+    };
+    insert_reminder(&mut *tx, &reminder).await?;
+    tx.commit().await?;
+    Ok(true)
+}
+
+pub async fn get_user_timezone(pool: &PgPool, user_id: &str) -> Result<Option<Tz>, sqlx::Error> {
+    let tz_name = sqlx::query_scalar!(
+        r#"SELECT tz_name FROM user_timezones WHERE user_id = $1"#,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(tz_name.and_then(|name| name.parse::<Tz>().ok()))
+}
+
+pub async fn set_user_timezone(pool: &PgPool, user_id: &str, tz: Tz) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO user_timezones (user_id, tz_name)
+        VALUES ($1, $2)
+        ON CONFLICT (user_id) DO UPDATE SET tz_name = EXCLUDED.tz_name
+        "#,
+        user_id,
+        tz.name()
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Someone who asked for the daily digest with `!digest on`.
+#[derive(Debug, PartialEq)]
+pub struct DigestSubscriber {
+    pub user_id: String,
+    /// The subscriber's own date the last digest went out on.
+    pub last_sent_on: Option<NaiveDate>,
+    pub timezone: Option<Tz>,
+}
+
+pub async fn set_digest(pool: &PgPool, user_id: &str, enabled: bool) -> Result<(), sqlx::Error> {
+    if enabled {
+        sqlx::query!(
+            r#"INSERT INTO digest_subscriptions (user_id) VALUES ($1) ON CONFLICT DO NOTHING"#,
+            user_id
+        )
+        .execute(pool)
+        .await?;
+    } else {
+        sqlx::query!(
+            r#"DELETE FROM digest_subscriptions WHERE user_id = $1"#,
+            user_id
+        )
+        .execute(pool)
+        .await?;
+    }
+    Ok(())
+}
+
+pub async fn get_digest_subscribers(pool: &PgPool) -> Result<Vec<DigestSubscriber>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT d.user_id, d.last_sent_on, t.tz_name AS "tz_name?"
+        FROM digest_subscriptions d
+        LEFT JOIN user_timezones t ON t.user_id = d.user_id
+        ORDER BY d.user_id
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|row| DigestSubscriber {
+            user_id: row.user_id,
+            last_sent_on: row.last_sent_on,
+            timezone: row.tz_name.and_then(|name| name.parse().ok()),
+        })
+        .collect())
+}
+
+pub async fn mark_digest_sent(
+    pool: &PgPool,
+    user_id: &str,
+    on: NaiveDate,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"UPDATE digest_subscriptions SET last_sent_on = $1 WHERE user_id = $2"#,
+        on,
+        user_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Every user's pending reminders due from `from` to `to`, soonest first.
+pub async fn get_reminders_due_between(
+    pool: &PgPool,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<Vec<Reminder>, sqlx::Error> {
+    sqlx::query_as!(
+        Reminder,
+        r#"
+        SELECT * FROM reminders
+        WHERE status = $1 AND deleted_at IS NULL AND trigger_time BETWEEN $2 AND $3
+        ORDER BY trigger_time
+        "#,
+        STATUS_PENDING,
+        from,
+        to
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn get_user_locale(pool: &PgPool, user_id: &str) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"SELECT locale FROM user_locales WHERE user_id = $1"#,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+pub async fn set_user_locale(
+    pool: &PgPool,
+    user_id: &str,
+    locale: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO user_locales (user_id, locale)
+        VALUES ($1, $2)
+        ON CONFLICT (user_id) DO UPDATE SET locale = EXCLUDED.locale
+        "#,
+        user_id,
+        locale
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// A server's overrides, `None` where it uses the global setting.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GuildSettings {
+    pub prefix: Option<String>,
+    pub max_reminders_per_user: Option<i64>,
+}
+
+pub async fn get_guild_settings(
+    pool: &PgPool,
+    guild_id: &str,
+) -> Result<GuildSettings, sqlx::Error> {
+    let settings = sqlx::query_as!(
+        GuildSettings,
+        r#"SELECT prefix, max_reminders_per_user FROM guild_settings WHERE guild_id = $1"#,
+        guild_id
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(settings.unwrap_or_default())
+}
+
+/// `None` goes back to the global prefix.
+pub async fn set_guild_prefix(
+    pool: &PgPool,
+    guild_id: &str,
+    prefix: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO guild_settings (guild_id, prefix)
+        VALUES ($1, $2)
+        ON CONFLICT (guild_id) DO UPDATE SET prefix = EXCLUDED.prefix
+        "#,
+        guild_id,
+        prefix
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// `None` goes back to the global limit.
+pub async fn set_guild_reminder_limit(
+    pool: &PgPool,
+    guild_id: &str,
+    limit: Option<i64>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO guild_settings (guild_id, max_reminders_per_user)
+        VALUES ($1, $2)
+        ON CONFLICT (guild_id) DO UPDATE SET max_reminders_per_user = EXCLUDED.max_reminders_per_user
+        "#,
+        guild_id,
+        limit
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+pub struct Metrics {
+    pub pending: i64,
+    pub next_trigger: Option<DateTime<Utc>>,
+}
+
+pub async fn get_metrics(pool: &PgPool) -> Result<Metrics, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT COUNT(*) AS "pending!", MIN(trigger_time) AS next_trigger
+        FROM reminders WHERE status = $1
+        "#,
+        STATUS_PENDING
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(Metrics {
+        pending: row.pending,
+        next_trigger: row.next_trigger,
+    })
+}
+
+#[derive(Debug)]
+pub struct ReminderStats {
+    pub pending: i64,
+    /// Earliest trigger time still pending, overdue ones included.
+    pub oldest: Option<DateTime<Utc>>,
+    /// Next trigger time that hasn't come yet.
+    pub soonest: Option<DateTime<Utc>>,
+    /// Pending reminders per source, `unknown` for ones from before it was kept.
+    pub by_source: Vec<(String, i64)>,
+}
+
+pub async fn get_reminder_stats(pool: &PgPool) -> Result<ReminderStats, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT COUNT(*) AS "pending!", MIN(trigger_time) AS oldest,
+            MIN(trigger_time) FILTER (WHERE trigger_time >= $2) AS soonest
+        FROM reminders WHERE status = $1
+        "#,
+        STATUS_PENDING,
+        Utc::now()
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(ReminderStats {
+        pending: row.pending,
+        oldest: row.oldest,
+        soonest: row.soonest,
+        by_source: count_reminders_by_source(pool).await?,
+    })
+}
+
+/// Pending reminders due from `from` to `to`, both included, counted per
+/// hour they're due in.
+pub async fn count_reminders_due_between(
+    pool: &PgPool,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<Vec<(DateTime<Utc>, i64)>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT date_trunc('hour', trigger_time) AS "hour!", COUNT(*) AS "count!"
+        FROM reminders
+        WHERE status = $1 AND deleted_at IS NULL AND trigger_time BETWEEN $2 AND $3
+        GROUP BY 1 ORDER BY 1
+        "#,
+        STATUS_PENDING,
+        from,
+        to
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().map(|row| (row.hour, row.count)).collect())
+}
+
+/// Pending reminders that were due before `now`.
+pub async fn count_overdue_reminders(
+    pool: &PgPool,
+    now: DateTime<Utc>,
+) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) AS "count!" FROM reminders
+        WHERE status = $1 AND deleted_at IS NULL AND trigger_time < $2
+        "#,
+        STATUS_PENDING,
+        now
+    )
+    .fetch_one(pool)
+    .await
+}
+
+pub async fn count_reminders_by_source(pool: &PgPool) -> Result<Vec<(String, i64)>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT COALESCE(source, 'unknown') AS "source!", COUNT(*) AS "count!"
+        FROM reminders WHERE status = $1
+        GROUP BY 1 ORDER BY 2 DESC, 1
+        "#,
+        STATUS_PENDING
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|row| (row.source, row.count))
+        .collect())
+}
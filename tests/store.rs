@@ -0,0 +1,667 @@
+//! Runs the reminder queries against a throwaway Postgres, so it needs Docker:
+//! `cargo test -- --ignored`.
+
+use chrono::{Duration, DurationRound, NaiveDate, Utc};
+use remindme_bot::store::{
+    cancel_all_reminders_for_user, claim_reminder, count_overdue_reminders,
+    count_reminders_by_source, count_reminders_due_between, delete_reminder,
+    find_user_reminders_by_text, get_digest_subscribers, get_due_reminders, get_guild_settings,
+    get_user_reminders, insert_reminder, last_user_reminder, mark_digest_sent, missing_columns,
+    purge_deleted_reminders, record_failed_attempt, reminder_table_columns, reschedule_reminder,
+    resolve_user_reminder, set_digest, set_guild_prefix, set_guild_reminder_limit,
+    set_user_timezone, update_reminder_text, DigestSubscriber, GuildSettings, Reminder,
+    PRIORITY_HIGH, PRIORITY_NORMAL, REMINDER_COLUMNS, SOURCE_SLASH, SOURCE_TEXT, STATUS_FAILED,
+    STATUS_PENDING, STATUS_SENT,
+};
+use sqlx::PgPool;
+use testcontainers_modules::postgres::Postgres;
+use testcontainers_modules::testcontainers::runners::AsyncRunner;
+use testcontainers_modules::testcontainers::ContainerAsync;
+
+async fn start_database() -> (ContainerAsync<Postgres>, PgPool) {
+    let container = Postgres::default().start().await.unwrap();
+    let url = format!(
+        "postgres://postgres:postgres@{}:{}/postgres",
+        container.get_host().await.unwrap(),
+        container.get_host_port_ipv4(5432).await.unwrap()
+    );
+    let pool = PgPool::connect(&url).await.unwrap();
+    sqlx::migrate!().run(&pool).await.unwrap();
+    // The container is stopped when dropped, so it has to outlive the pool.
+    (container, pool)
+}
+
+fn reminder(message_id: &str, message_content: &str, trigger_in: Duration) -> Reminder {
+    Reminder {
+        id: None,
+        user_id: "1".to_string(),
+        target_user_id: "1".to_string(),
+        message_id: message_id.to_string(),
+        message_content: message_content.to_string(),
+        trigger_time: Utc::now() + trigger_in,
+        channel_id: "3".to_string(),
+        recurrence_interval: None,
+        status: STATUS_PENDING.to_string(),
+        attempts: 0,
+        deliver_dm: false,
+        remaining_occurrences: None,
+        created_at: None,
+        target_role_id: None,
+        priority: PRIORITY_NORMAL,
+        deleted_at: None,
+        tag: None,
+        retry_at: None,
+        source: Some(SOURCE_TEXT.to_string()),
+    }
+}
+
+#[test]
+fn missing_columns_are_listed_in_order() {
+    let actual = vec!["id".to_string(), "status".to_string()];
+    assert_eq!(
+        missing_columns(&["id", "source", "status", "created_at"], &actual),
+        vec!["source", "created_at"]
+    );
+    assert!(missing_columns(&["id", "status"], &actual).is_empty());
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn migrations_create_every_reminder_column() {
+    let (_container, pool) = start_database().await;
+
+    let columns = reminder_table_columns(&pool).await.unwrap();
+    assert_eq!(
+        missing_columns(REMINDER_COLUMNS, &columns),
+        Vec::<&str>::new()
+    );
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn only_due_pending_reminders_are_returned() {
+    let (_container, pool) = start_database().await;
+
+    insert_reminder(&pool, &reminder("2", "overdue", Duration::hours(-1)))
+        .await
+        .unwrap();
+    insert_reminder(&pool, &reminder("3", "upcoming", Duration::hours(1)))
+        .await
+        .unwrap();
+    let mut claimed = reminder("4", "already sending", Duration::hours(-1));
+    claimed.status = STATUS_SENT.to_string();
+    insert_reminder(&pool, &claimed).await.unwrap();
+
+    let due = get_due_reminders(&pool).await.unwrap();
+    let contents: Vec<&str> = due
+        .iter()
+        .map(|reminder| reminder.message_content.as_str())
+        .collect();
+    assert_eq!(contents, vec!["overdue"]);
+    assert!(due[0].id.is_some());
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn the_due_query_uses_an_index() {
+    let (_container, pool) = start_database().await;
+
+    // Mostly reminders that already went out, as in a table that has been
+    // running for a while.
+    sqlx::query(
+        r#"
+        INSERT INTO reminders
+            (user_id, target_user_id, message_id, message_content, trigger_time, channel_id, status)
+        SELECT '1', '1', n::text, 'seeded', now() + (n - 19000) * interval '1 minute', '3',
+            CASE WHEN n < 19000 THEN $1 ELSE $2 END
+        FROM generate_series(1, 20000) AS n
+        "#,
+    )
+    .bind(STATUS_SENT)
+    .bind(STATUS_PENDING)
+    .execute(&pool)
+    .await
+    .unwrap();
+    sqlx::query("ANALYZE reminders")
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    // Same query as `get_due_reminders`.
+    let plan: Vec<String> = sqlx::query_scalar(
+        r#"
+        EXPLAIN SELECT * FROM reminders
+        WHERE trigger_time < $1 AND status = $2 AND deleted_at IS NULL
+            AND (retry_at IS NULL OR retry_at <= $1)
+        ORDER BY priority DESC, trigger_time
+        "#,
+    )
+    .bind(Utc::now())
+    .bind(STATUS_PENDING)
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    let plan = plan.join("\n");
+    assert!(plan.contains("Index Scan on reminders_"), "{}", plan);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn the_same_message_only_sets_one_reminder() {
+    let (_container, pool) = start_database().await;
+
+    let first = reminder("2", "stretch", Duration::hours(1));
+    let redelivered = reminder("2", "stretch", Duration::hours(1));
+    assert!(insert_reminder(&pool, &first).await.unwrap());
+    assert!(!insert_reminder(&pool, &redelivered).await.unwrap());
+
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM reminders WHERE message_id = $1")
+        .bind(&first.message_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(count, 1);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn last_reminder_is_the_users_most_recent() {
+    let (_container, pool) = start_database().await;
+
+    insert_reminder(&pool, &reminder("2", "first", Duration::hours(2)))
+        .await
+        .unwrap();
+    insert_reminder(&pool, &reminder("3", "second", Duration::hours(1)))
+        .await
+        .unwrap();
+    let mut someone_else = reminder("4", "not yours", Duration::hours(3));
+    someone_else.user_id = "5".to_string();
+    insert_reminder(&pool, &someone_else).await.unwrap();
+
+    let last = last_user_reminder(&pool, "1").await.unwrap().unwrap();
+    assert_eq!(last.message_content, "second");
+    assert!(last_user_reminder(&pool, "6").await.unwrap().is_none());
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn pending_reminders_are_counted_by_source() {
+    let (_container, pool) = start_database().await;
+
+    for message_id in ["2", "3"] {
+        insert_reminder(&pool, &reminder(message_id, "typed", Duration::hours(1)))
+            .await
+            .unwrap();
+    }
+    let mut slash = reminder("4", "slash", Duration::hours(1));
+    slash.source = Some(SOURCE_SLASH.to_string());
+    insert_reminder(&pool, &slash).await.unwrap();
+    let mut old = reminder("5", "old", Duration::hours(1));
+    old.source = None;
+    insert_reminder(&pool, &old).await.unwrap();
+    let mut sent = reminder("6", "sent", Duration::hours(-1));
+    sent.status = STATUS_SENT.to_string();
+    insert_reminder(&pool, &sent).await.unwrap();
+
+    assert_eq!(
+        count_reminders_by_source(&pool).await.unwrap(),
+        vec![
+            ("text".to_string(), 2),
+            ("slash".to_string(), 1),
+            ("unknown".to_string(), 1),
+        ]
+    );
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn reminders_are_found_by_a_fragment_of_their_text() {
+    let (_container, pool) = start_database().await;
+
+    insert_reminder(
+        &pool,
+        &reminder("2", "Water the plants", Duration::hours(2)),
+    )
+    .await
+    .unwrap();
+    insert_reminder(&pool, &reminder("3", "water the lawn", Duration::hours(1)))
+        .await
+        .unwrap();
+    insert_reminder(&pool, &reminder("4", "50% off sale", Duration::hours(1)))
+        .await
+        .unwrap();
+
+    let contents = |reminders: Vec<Reminder>| {
+        reminders
+            .into_iter()
+            .map(|reminder| reminder.message_content)
+            .collect::<Vec<_>>()
+    };
+    let find = |fragment| find_user_reminders_by_text(&pool, "1", fragment);
+    assert_eq!(
+        contents(find("WATER").await.unwrap()),
+        vec!["water the lawn", "Water the plants"]
+    );
+    assert_eq!(
+        contents(find("plants").await.unwrap()),
+        vec!["Water the plants"]
+    );
+    assert_eq!(contents(find("%").await.unwrap()), vec!["50% off sale"]);
+    assert!(find("dentist").await.unwrap().is_empty());
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn reminders_due_in_a_window_are_counted_per_hour() {
+    let (_container, pool) = start_database().await;
+
+    let now = Utc::now();
+    let hour = |offset: i64| {
+        (now + Duration::hours(offset))
+            .duration_trunc(Duration::hours(1))
+            .unwrap()
+    };
+    let at = |message_id: &str, trigger_time| Reminder {
+        trigger_time,
+        ..reminder(message_id, "upcoming", Duration::zero())
+    };
+    insert_reminder(&pool, &at("2", hour(2) + Duration::minutes(10)))
+        .await
+        .unwrap();
+    insert_reminder(&pool, &at("3", hour(2) + Duration::minutes(50)))
+        .await
+        .unwrap();
+    insert_reminder(&pool, &at("4", hour(5))).await.unwrap();
+    insert_reminder(&pool, &at("5", hour(30))).await.unwrap();
+    let mut sent = at("6", hour(3));
+    sent.status = STATUS_SENT.to_string();
+    insert_reminder(&pool, &sent).await.unwrap();
+
+    let counts = count_reminders_due_between(&pool, now, now + Duration::hours(24))
+        .await
+        .unwrap();
+    assert_eq!(counts, vec![(hour(2), 2), (hour(5), 1)]);
+    assert!(
+        count_reminders_due_between(&pool, now - Duration::hours(2), now)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn tags_are_kept_with_the_reminder() {
+    let (_container, pool) = start_database().await;
+
+    let mut report = reminder("2", "send the report", Duration::hours(1));
+    report.tag = Some("work".to_string());
+    insert_reminder(&pool, &report).await.unwrap();
+    insert_reminder(&pool, &reminder("3", "call mom", Duration::hours(2)))
+        .await
+        .unwrap();
+
+    let tags: Vec<Option<String>> = get_user_reminders(&pool, "1")
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|reminder| reminder.tag)
+        .collect();
+    assert_eq!(tags, vec![Some("work".to_string()), None]);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn digest_subscribers_come_with_their_timezone() {
+    let (_container, pool) = start_database().await;
+
+    set_digest(&pool, "1", true).await.unwrap();
+    set_digest(&pool, "1", true).await.unwrap();
+    set_user_timezone(&pool, "1", chrono_tz::Europe::Warsaw)
+        .await
+        .unwrap();
+    set_digest(&pool, "2", true).await.unwrap();
+    set_digest(&pool, "3", true).await.unwrap();
+    set_digest(&pool, "3", false).await.unwrap();
+    let today = NaiveDate::from_ymd_opt(2024, 3, 6).unwrap();
+    mark_digest_sent(&pool, "2", today).await.unwrap();
+
+    assert_eq!(
+        get_digest_subscribers(&pool).await.unwrap(),
+        vec![
+            DigestSubscriber {
+                user_id: "1".to_string(),
+                last_sent_on: None,
+                timezone: Some(chrono_tz::Europe::Warsaw),
+            },
+            DigestSubscriber {
+                user_id: "2".to_string(),
+                last_sent_on: Some(today),
+                timezone: None,
+            },
+        ]
+    );
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn failed_reminders_wait_until_their_retry_time() {
+    let (_container, pool) = start_database().await;
+
+    insert_reminder(&pool, &reminder("2", "overdue", Duration::hours(-1)))
+        .await
+        .unwrap();
+    let id = get_due_reminders(&pool).await.unwrap()[0].id.unwrap();
+
+    let in_a_minute = Utc::now() + Duration::minutes(1);
+    assert!(!record_failed_attempt(&pool, id, 5, in_a_minute)
+        .await
+        .unwrap());
+    assert!(get_due_reminders(&pool).await.unwrap().is_empty());
+
+    let a_minute_ago = Utc::now() - Duration::minutes(1);
+    assert!(!record_failed_attempt(&pool, id, 5, a_minute_ago)
+        .await
+        .unwrap());
+    let due = get_due_reminders(&pool).await.unwrap();
+    assert_eq!(due.len(), 1);
+    assert_eq!(due[0].attempts, 2);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn reminders_are_retired_after_the_last_attempt() {
+    let (_container, pool) = start_database().await;
+
+    insert_reminder(&pool, &reminder("2", "overdue", Duration::hours(-1)))
+        .await
+        .unwrap();
+    let id = get_due_reminders(&pool).await.unwrap()[0].id.unwrap();
+
+    let a_minute_ago = Utc::now() - Duration::minutes(1);
+    for _ in 1..5 {
+        assert!(!record_failed_attempt(&pool, id, 5, a_minute_ago)
+            .await
+            .unwrap());
+        assert_eq!(get_due_reminders(&pool).await.unwrap().len(), 1);
+    }
+    assert!(record_failed_attempt(&pool, id, 5, a_minute_ago)
+        .await
+        .unwrap());
+    assert!(get_due_reminders(&pool).await.unwrap().is_empty());
+
+    let (status, attempts): (String, i32) =
+        sqlx::query_as("SELECT status, attempts FROM reminders WHERE id = $1")
+            .bind(id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(status, STATUS_FAILED);
+    assert_eq!(attempts, 5);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn high_priority_reminders_come_first() {
+    let (_container, pool) = start_database().await;
+
+    insert_reminder(&pool, &reminder("2", "oldest", Duration::hours(-3)))
+        .await
+        .unwrap();
+    let mut urgent = reminder("3", "urgent", Duration::hours(-1));
+    urgent.priority = PRIORITY_HIGH;
+    insert_reminder(&pool, &urgent).await.unwrap();
+    insert_reminder(&pool, &reminder("4", "newer", Duration::hours(-2)))
+        .await
+        .unwrap();
+
+    let contents: Vec<String> = get_due_reminders(&pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|reminder| reminder.message_content)
+        .collect();
+    assert_eq!(contents, vec!["urgent", "oldest", "newer"]);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn only_delivered_reminders_are_removed() {
+    let (_container, pool) = start_database().await;
+
+    insert_reminder(&pool, &reminder("2", "delivered", Duration::hours(-2)))
+        .await
+        .unwrap();
+    insert_reminder(&pool, &reminder("3", "undelivered", Duration::hours(-1)))
+        .await
+        .unwrap();
+    let due = get_due_reminders(&pool).await.unwrap();
+    let (delivered, undelivered) = (due[0].id.unwrap(), due[1].id.unwrap());
+    assert!(claim_reminder(&pool, delivered).await.unwrap());
+    assert!(claim_reminder(&pool, undelivered).await.unwrap());
+
+    // What `finish_reminder` does after a successful and a failed send.
+    delete_reminder(&pool, delivered).await.unwrap();
+    let a_minute_ago = Utc::now() - Duration::minutes(1);
+    assert!(!record_failed_attempt(&pool, undelivered, 5, a_minute_ago)
+        .await
+        .unwrap());
+
+    let rows: Vec<(i32, bool)> =
+        sqlx::query_as("SELECT id, deleted_at IS NOT NULL FROM reminders ORDER BY id")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    assert_eq!(rows, vec![(delivered, true), (undelivered, false)]);
+    let due = get_due_reminders(&pool).await.unwrap();
+    assert_eq!(due.len(), 1);
+    assert_eq!(due[0].message_content, "undelivered");
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn cancelling_all_only_touches_the_callers_reminders() {
+    let (_container, pool) = start_database().await;
+
+    insert_reminder(&pool, &reminder("2", "stretch", Duration::hours(1)))
+        .await
+        .unwrap();
+    insert_reminder(
+        &pool,
+        &reminder("3", "water the plants", Duration::hours(2)),
+    )
+    .await
+    .unwrap();
+    let mut someone_elses = reminder("4", "call mom", Duration::hours(1));
+    someone_elses.user_id = "5".to_string();
+    insert_reminder(&pool, &someone_elses).await.unwrap();
+
+    assert_eq!(cancel_all_reminders_for_user(&pool, "1").await.unwrap(), 2);
+    assert!(get_user_reminders(&pool, "1").await.unwrap().is_empty());
+    let left = get_user_reminders(&pool, "5").await.unwrap();
+    assert_eq!(left.len(), 1);
+    assert_eq!(left[0].message_content, "call mom");
+    assert_eq!(cancel_all_reminders_for_user(&pool, "1").await.unwrap(), 0);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn edits_only_reach_the_owners_reminder() {
+    let (_container, pool) = start_database().await;
+
+    insert_reminder(&pool, &reminder("2", "stretch", Duration::hours(1)))
+        .await
+        .unwrap();
+    insert_reminder(
+        &pool,
+        &reminder("3", "water the plants", Duration::hours(2)),
+    )
+    .await
+    .unwrap();
+    let mut someone_elses = reminder("4", "call mom", Duration::hours(1));
+    someone_elses.user_id = "5".to_string();
+    insert_reminder(&pool, &someone_elses).await.unwrap();
+
+    // Numbers are 1-based over the caller's own reminders.
+    assert!(resolve_user_reminder(&pool, "1", 0)
+        .await
+        .unwrap()
+        .is_none());
+    assert!(resolve_user_reminder(&pool, "1", 3)
+        .await
+        .unwrap()
+        .is_none());
+    let second = resolve_user_reminder(&pool, "1", 2).await.unwrap().unwrap();
+    assert_eq!(second.message_content, "water the plants");
+
+    let other_id = get_user_reminders(&pool, "5").await.unwrap()[0].id.unwrap();
+    assert!(!update_reminder_text(&pool, "1", other_id, "hijacked")
+        .await
+        .unwrap());
+
+    assert!(
+        update_reminder_text(&pool, "1", second.id.unwrap(), "water the lawn")
+            .await
+            .unwrap()
+    );
+    let texts: Vec<String> = get_user_reminders(&pool, "1")
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|reminder| reminder.message_content)
+        .collect();
+    assert_eq!(texts, vec!["stretch", "water the lawn"]);
+    let other = get_user_reminders(&pool, "5").await.unwrap();
+    assert_eq!(other[0].message_content, "call mom");
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn deleted_reminders_are_kept_until_purged() {
+    let (_container, pool) = start_database().await;
+
+    insert_reminder(&pool, &reminder("2", "overdue", Duration::hours(-1)))
+        .await
+        .unwrap();
+    let id = get_due_reminders(&pool).await.unwrap()[0].id.unwrap();
+    delete_reminder(&pool, id).await.unwrap();
+
+    assert!(get_due_reminders(&pool).await.unwrap().is_empty());
+    let count = || async {
+        sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM reminders")
+            .fetch_one(&pool)
+            .await
+            .unwrap()
+    };
+    assert_eq!(count().await, 1);
+
+    let day_ago = Utc::now() - Duration::days(1);
+    assert_eq!(purge_deleted_reminders(&pool, day_ago).await.unwrap(), 0);
+    assert_eq!(count().await, 1);
+    assert_eq!(purge_deleted_reminders(&pool, Utc::now()).await.unwrap(), 1);
+    assert_eq!(count().await, 0);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn only_the_owner_can_reschedule_a_pending_reminder() {
+    let (_container, pool) = start_database().await;
+
+    insert_reminder(&pool, &reminder("2", "stretch", Duration::hours(1)))
+        .await
+        .unwrap();
+    let mut sent = reminder("3", "already out", Duration::hours(-1));
+    sent.status = STATUS_SENT.to_string();
+    insert_reminder(&pool, &sent).await.unwrap();
+    let id = last_user_reminder(&pool, "1")
+        .await
+        .unwrap()
+        .unwrap()
+        .id
+        .unwrap();
+    let new_time = (Utc::now() + Duration::days(1))
+        .duration_trunc(Duration::seconds(1))
+        .unwrap();
+
+    assert!(!reschedule_reminder(&pool, "2", id, new_time).await.unwrap());
+    assert!(reschedule_reminder(&pool, "1", id, new_time).await.unwrap());
+    let reminders = get_user_reminders(&pool, "1").await.unwrap();
+    assert_eq!(reminders.len(), 1);
+    assert_eq!(reminders[0].trigger_time, new_time);
+
+    let sent_id = sqlx::query_scalar::<_, i32>("SELECT id FROM reminders WHERE status = $1")
+        .bind(STATUS_SENT)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert!(!reschedule_reminder(&pool, "1", sent_id, new_time)
+        .await
+        .unwrap());
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn overdue_reminders_are_counted() {
+    let (_container, pool) = start_database().await;
+
+    insert_reminder(&pool, &reminder("2", "overdue", Duration::hours(-2)))
+        .await
+        .unwrap();
+    insert_reminder(&pool, &reminder("3", "just due", Duration::minutes(-1)))
+        .await
+        .unwrap();
+    insert_reminder(&pool, &reminder("4", "later", Duration::hours(1)))
+        .await
+        .unwrap();
+    assert_eq!(count_overdue_reminders(&pool, Utc::now()).await.unwrap(), 2);
+    let an_hour_ago = Utc::now() - Duration::hours(1);
+    assert_eq!(
+        count_overdue_reminders(&pool, an_hour_ago).await.unwrap(),
+        1
+    );
+
+    let id = get_due_reminders(&pool).await.unwrap()[0].id.unwrap();
+    delete_reminder(&pool, id).await.unwrap();
+    assert_eq!(count_overdue_reminders(&pool, Utc::now()).await.unwrap(), 1);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn guild_settings_are_kept_per_server() {
+    let (_container, pool) = start_database().await;
+
+    assert_eq!(
+        get_guild_settings(&pool, "7").await.unwrap(),
+        GuildSettings::default()
+    );
+
+    set_guild_prefix(&pool, "7", Some("?")).await.unwrap();
+    set_guild_reminder_limit(&pool, "7", Some(5)).await.unwrap();
+    set_guild_reminder_limit(&pool, "8", Some(10))
+        .await
+        .unwrap();
+    assert_eq!(
+        get_guild_settings(&pool, "7").await.unwrap(),
+        GuildSettings {
+            prefix: Some("?".to_string()),
+            max_reminders_per_user: Some(5),
+        }
+    );
+
+    // Going back to the default for one setting leaves the other alone.
+    set_guild_prefix(&pool, "7", None).await.unwrap();
+    assert_eq!(
+        get_guild_settings(&pool, "7").await.unwrap(),
+        GuildSettings {
+            prefix: None,
+            max_reminders_per_user: Some(5),
+        }
+    );
+    assert_eq!(
+        get_guild_settings(&pool, "8")
+            .await
+            .unwrap()
+            .max_reminders_per_user,
+        Some(10)
+    );
+}